@@ -0,0 +1,245 @@
+// Solving many mazes at once, e.g. a whole directory of inputs every night.
+// The files are solved in parallel and the solved images are written to an output
+// directory that mirrors the input one.
+
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use glob::glob;
+use rayon::prelude::*;
+use serde_json::{json, Value};
+
+use super::{Connectivity, CostModel, ImageFormat, KeyRules, KeySize, Maze, MazeError, MazeErrorKind, MazeResult, Palette, Portal, Schedule, Topology};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BatchStatus {
+	Solved,
+	NoStart,
+	NoEnd,
+	Error,
+}
+
+// the outcome of one file of the batch
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BatchEntry {
+	pub input: PathBuf,
+	// where the solved image was written, None if it wasn't
+	pub output: Option<PathBuf>,
+	pub status: BatchStatus,
+	// the message of the error for every status but Solved
+	pub error: Option<String>,
+	pub cost: Option<u32>,
+	pub length: usize,
+	pub keys: usize,
+	pub explored: u32,
+	// wall-clock time of loading, solving and saving
+	pub time: Duration,
+}
+
+// settings shared by all files of a batch
+#[derive(Clone, Debug, Default)]
+pub struct Batch {
+	pub palette: Palette,
+	pub key_size: KeySize,
+	pub key_rules: KeyRules,
+	pub connectivity: Connectivity,
+	pub topology: Topology,
+	pub portals: Vec<Portal>,
+	pub schedules: Vec<Schedule>,
+	// GreyCost if None
+	pub cost_model: Option<Arc<dyn CostModel>>,
+	pub astar: bool,
+	// solved images go here, at the same relative path as the input; None only solves
+	pub output_dir: Option<PathBuf>,
+	// number of threads, 0 uses every core
+	pub jobs: usize,
+}
+
+fn has_wildcards(text: &str) -> bool {
+	text.contains(['*', '?', '['])
+}
+
+// Files matched by a directory (every maze file in it, recursively) or a glob pattern,
+// together with the directory their output paths are relative to.
+pub fn list_inputs(pattern: &str) -> MazeResult<(PathBuf, Vec<PathBuf>)> {
+	let path = Path::new(pattern);
+	let (base, pattern, only_mazes) = match path.is_dir() {
+		true => (path.to_path_buf(), path.join("**").join("*").to_string_lossy().into_owned(), true),
+		false => {
+			// основата е частта от пътя преди първия компонент със заместващи символи
+			let base: PathBuf = path.components()
+				.take_while(|c| !has_wildcards(&c.as_os_str().to_string_lossy()))
+				.collect();
+			let base = match base == path {
+				true => path.parent().map(Path::to_path_buf).unwrap_or_default(),
+				false => base
+			};
+			(base, pattern.to_string(), false)
+		}
+	};
+
+	let paths = glob(&pattern).map_err(|e| MazeError::new(
+		MazeErrorKind::Other,
+		format!("Invalid pattern {}: {}", pattern, e),
+	).caused_by(e))?;
+
+	let mut inputs: Vec<PathBuf> = Vec::new();
+	for entry in paths {
+		let entry = entry.map_err(|e| MazeError::from(std::io::Error::from(e)).context(format!("Failed to list {}", pattern)))?;
+		if entry.is_file() && (!only_mazes || ImageFormat::from_extension(&entry.to_string_lossy()).is_some()) {
+			inputs.push(entry);
+		}
+	}
+
+	if inputs.is_empty() {
+		let not_found = std::io::Error::new(std::io::ErrorKind::NotFound, "no matching files");
+		return Err(MazeError::from(not_found).context(format!("No mazes found in {}", pattern)));
+	}
+	Ok((base, inputs))
+}
+
+impl Batch {
+	// Solves every file matched by pattern (see list_inputs), the entries are in the order of the files.
+	pub fn run(&self, pattern: &str) -> MazeResult<Vec<BatchEntry>> {
+		let (base, inputs) = list_inputs(pattern)?;
+
+		let solve_all = || -> Vec<BatchEntry> {
+			inputs.par_iter().map(|input| self.solve_file(&base, input)).collect()
+		};
+
+		match self.jobs {
+			0 => Ok(solve_all()),
+			jobs => {
+				let pool = rayon::ThreadPoolBuilder::new()
+					.num_threads(jobs)
+					.build()
+					.map_err(|e| MazeError::new(MazeErrorKind::Other, format!("Failed to start {} threads: {}", jobs, e)).caused_by(e))?;
+				Ok(pool.install(solve_all))
+			}
+		}
+	}
+
+	fn output_path(&self, base: &Path, input: &Path) -> Option<PathBuf> {
+		let output_dir = self.output_dir.as_ref()?;
+		// glob drops the leading "./", so it's skipped on both sides
+		let without_cur_dir = |path: &Path| -> PathBuf {
+			path.components().filter(|c| *c != Component::CurDir).collect()
+		};
+		let (base, input) = (without_cur_dir(base), without_cur_dir(input));
+		let relative = input.strip_prefix(&base).unwrap_or(&input);
+		// абсолютни пътища и ".." не бива да излизат от изходната директория
+		let relative: PathBuf = relative.components()
+			.filter(|c| matches!(c, Component::Normal(_)))
+			.collect();
+		Some(output_dir.join(relative))
+	}
+
+	pub fn solve_file(&self, base: &Path, input: &Path) -> BatchEntry {
+		let started = Instant::now();
+		let mut entry = BatchEntry {
+			input: input.to_path_buf(),
+			output: None,
+			status: BatchStatus::Solved,
+			error: None,
+			cost: None,
+			length: 0,
+			keys: 0,
+			explored: 0,
+			time: Duration::default(),
+		};
+
+		let output = self.output_path(base, input);
+		if let Err(e) = self.solve_into(&mut entry, output) {
+			entry.status = match e.kind {
+				MazeErrorKind::NoStart => BatchStatus::NoStart,
+				MazeErrorKind::NoEnd | MazeErrorKind::Unreachable => BatchStatus::NoEnd,
+				_ => BatchStatus::Error
+			};
+			entry.error = Some(e.to_string());
+		}
+
+		entry.time = started.elapsed();
+		entry
+	}
+
+	fn solve_into(&self, entry: &mut BatchEntry, output: Option<PathBuf>) -> MazeResult<()> {
+		let mut maze = Maze::open_with_palette(&entry.input.to_string_lossy(), self.palette)?;
+		maze.set_key_rules(self.key_rules);
+		maze.set_connectivity(self.connectivity);
+		maze.set_topology(self.topology)?;
+		maze.set_portals(self.portals.clone());
+		maze.set_schedules(self.schedules.clone());
+		if let Some(cost_model) = &self.cost_model {
+			maze.set_cost_model(cost_model.clone());
+		}
+		let found = maze.find_path_with(self.key_size, self.astar);
+		entry.explored = maze.states_explored();
+		found?;
+
+		let solution = maze.solution()?;
+		entry.cost = Some(solution.cost);
+		entry.length = solution.len();
+		entry.keys = solution.pickups.len();
+
+		if let Some(output) = output {
+			if let Some(dir) = output.parent() {
+				fs::create_dir_all(dir).map_err(|e| MazeError::from(e).context(format!("Failed to create {}", dir.display())))?;
+			}
+			maze.save_path(&output.to_string_lossy())?;
+			entry.output = Some(output);
+		}
+
+		Ok(())
+	}
+}
+
+fn csv_field(text: &str) -> String {
+	match text.contains([',', '"', '\n']) {
+		true => format!("\"{}\"", text.replace('"', "\"\"")),
+		false => text.to_string()
+	}
+}
+
+fn time_ms(time: &Duration) -> String {
+	format!("{:.3}", time.as_secs_f64() * 1000.0)
+}
+
+// one line per file, with a header
+pub fn report_csv(entries: &[BatchEntry]) -> String {
+	let mut csv = String::from("file,status,cost,length,keys,explored,time_ms,output,error\n");
+	for entry in entries {
+		let fields = [
+			csv_field(&entry.input.to_string_lossy()),
+			format!("{:?}", entry.status),
+			entry.cost.map(|cost| cost.to_string()).unwrap_or_default(),
+			entry.length.to_string(),
+			entry.keys.to_string(),
+			entry.explored.to_string(),
+			time_ms(&entry.time),
+			entry.output.as_ref().map(|output| csv_field(&output.to_string_lossy())).unwrap_or_default(),
+			entry.error.as_ref().map(|error| csv_field(error)).unwrap_or_default(),
+		];
+		csv.push_str(&fields.join(","));
+		csv.push('\n');
+	}
+	csv
+}
+
+// a JSON array with one object per file
+pub fn report_json(entries: &[BatchEntry]) -> String {
+	let objects: Vec<Value> = entries.iter().map(|entry| json!({
+		"file": entry.input.to_string_lossy(),
+		"status": format!("{:?}", entry.status),
+		"cost": entry.cost,
+		"length": entry.length,
+		"keys": entry.keys,
+		"explored": entry.explored,
+		"time_ms": (entry.time.as_secs_f64() * 1_000_000.0).round() / 1000.0,
+		"output": entry.output.as_ref().map(|output| output.to_string_lossy()),
+		"error": entry.error,
+	})).collect();
+
+	format!("{:#}\n", Value::Array(objects))
+}
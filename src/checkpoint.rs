@@ -0,0 +1,113 @@
+// Checkpoints the path has to pass before it may end: the waypoints given by the caller
+// and every area of the palette's checkpoint colour. Each of them is a bit of the visited
+// set carried by the search, so with up to EXACT_CHECKPOINTS of them the search finds
+// the best order to visit them in (a Dijkstra over the subsets, like a bitmask DP).
+//
+// Past that the layers would take too much memory, so the order is fixed up front:
+// starting from the first START pixel, the nearest not yet visited checkpoint by
+// Manhattan distance comes next. Walls and doors are not taken into account, so the
+// route is always valid but can be longer than the best one.
+
+use super::{Coord, Maze, MazeError, MazeErrorKind, MazeResult, PixelType, Progress, EXACT_CHECKPOINTS, MAX_DIST, NO_REGION};
+
+impl Maze {
+	// the waypoints used by the next search, in addition to the checkpoint areas
+	pub fn set_waypoints(&mut self, waypoints: Vec<Coord>) {
+		self.waypoints = waypoints;
+	}
+
+	pub fn waypoints(&self) -> &[Coord] {
+		&self.waypoints
+	}
+
+	// Numbers the waypoints and then the checkpoint areas in row-major order of their first pixel,
+	// and picks the visiting order if there are too many of them. starts are the START pixels.
+	pub(crate) fn find_checkpoints(&mut self, starts: &[usize]) -> MazeResult<()> {
+		for c in self.waypoints.clone() {
+			let indx = self.pixel_indx(&c)?;
+			if self.palette.classify(&self.colors[indx]) == PixelType::WALL {
+				return Err(MazeError::new(
+					MazeErrorKind::Config,
+					"A waypoint is on a wall.",
+				).at(c));
+			}
+
+			if !self.checkpoint_at.contains_key(&indx) {
+				self.checkpoint_at.insert(indx, self.checkpoints.len() as u32);
+				self.checkpoints.push(indx);
+			}
+		}
+
+		let mut areas: Vec<(u32, u32)> = Vec::new();
+		for indx in 0..self.colors.len() {
+			if self.palette.classify(&self.colors[indx]) != PixelType::CHECKPOINT || self.checkpoint_at.contains_key(&indx) {
+				continue;
+			}
+			if self.region_ids[indx] == NO_REGION {
+				let c = self.coord_at(indx);
+				self.set_area_at(&c);
+			}
+
+			let region_id = self.region_ids[indx];
+			let checkpoint = match areas.iter().find(|(id, _)| *id == region_id) {
+				Some((_, checkpoint)) => *checkpoint,
+				None => {
+					let checkpoint = self.checkpoints.len() as u32;
+					areas.push((region_id, checkpoint));
+					self.checkpoints.push(indx);
+					checkpoint
+				}
+			};
+			self.checkpoint_at.insert(indx, checkpoint);
+		}
+
+		if self.checkpoints.len() > EXACT_CHECKPOINTS {
+			self.checkpoint_order = self.nearest_neighbour_order(starts[0]);
+		}
+		Ok(())
+	}
+
+	// The progress a search from this source pixel starts with: a waypoint on it is visited from the start.
+	pub(crate) fn start_progress(&self, indx: usize) -> Progress {
+		match self.checkpoint_at.get(&indx) {
+			Some(checkpoint) if self.checkpoint_order.first().is_none_or(|first| first == checkpoint) => Progress::default().with_visited(*checkpoint),
+			_ => Progress::default()
+		}
+	}
+
+	fn nearest_neighbour_order(&self, start: usize) -> Vec<u32> {
+		let manhattan = |a: usize, b: usize| {
+			let (a, b) = (self.coord_at(a), self.coord_at(b));
+			a.row.abs_diff(b.row) + a.col.abs_diff(b.col)
+		};
+
+		let mut left: Vec<u32> = (0..self.checkpoints.len() as u32).collect();
+		let mut order: Vec<u32> = Vec::with_capacity(left.len());
+		let mut curr = start;
+		while let Some(i) = (0..left.len()).min_by_key(|i| manhattan(curr, self.checkpoints[left[*i] as usize])) {
+			let checkpoint = left.remove(i);
+			curr = self.checkpoints[checkpoint as usize];
+			order.push(checkpoint);
+		}
+		order
+	}
+
+	// Unreachable error for the checkpoints no search layer got to, None if every one was reached.
+	pub(crate) fn checkpoint_error(&self) -> Option<MazeError> {
+		let reached = |checkpoint: u32| self.checkpoint_at.iter()
+			.filter(|(_, number)| **number == checkpoint)
+			.any(|(indx, _)| self.dists.iter().any(|dists| dists[*indx] != MAX_DIST));
+
+		let unreached: Vec<Coord> = (0..self.checkpoints.len() as u32)
+			.filter(|checkpoint| !reached(*checkpoint))
+			.map(|checkpoint| self.coord_at(self.checkpoints[checkpoint as usize]))
+			.collect();
+
+		let first = *unreached.first()?;
+		let coords: Vec<String> = unreached.iter().map(|c| format!("({}, {})", c.row, c.col)).collect();
+		Some(MazeError::new(
+			MazeErrorKind::Unreachable,
+			format!("The end can't be reached, the checkpoints at {} are out of reach.", coords.join(", ")),
+		).at(first))
+	}
+}
@@ -0,0 +1,126 @@
+// Reader for the small config files shared by the team (palettes and such), in TOML:
+//
+//     # comment
+//     wall = [0, 0, 0]
+//     [palette]
+//     start = "#c3c3c4"
+//
+// and the equivalent JSON objects. Nested tables/objects are flattened to dotted keys,
+// so both of the above give "palette.start".
+
+use super::{Color, MazeError, MazeErrorKind, MazeResult};
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum ConfigValue {
+	Number(f64),
+	Bool(bool),
+	Text(String),
+	List(Vec<ConfigValue>),
+}
+
+impl ConfigValue {
+	pub(crate) fn as_u32(&self) -> Option<u32> {
+		match self {
+			ConfigValue::Number(n) if *n >= 0.0 && n.fract() == 0.0 && *n <= u32::MAX as f64 => Some(*n as u32),
+			_ => None
+		}
+	}
+
+	pub(crate) fn as_color(&self) -> Option<Color> {
+		match self {
+			ConfigValue::List(list) if list.len() == 3 => {
+				let mut channels = [0u8; 3];
+				for (channel, value) in channels.iter_mut().zip(list) {
+					*channel = match value.as_u32() {
+						Some(n) if n <= 255 => n as u8,
+						_ => return None
+					};
+				}
+				Some(Color { r: channels[0], g: channels[1], b: channels[2] })
+			},
+			ConfigValue::Text(text) => parse_hex_color(text),
+			_ => None
+		}
+	}
+}
+
+// "#rrggbb" or "rrggbb"
+pub(crate) fn parse_hex_color(text: &str) -> Option<Color> {
+	let hex = text.strip_prefix('#').unwrap_or(text);
+	if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+		return None;
+	}
+
+	let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+	Some(Color { r: channel(0)?, g: channel(2)?, b: channel(4)? })
+}
+
+pub(crate) fn config_error<T: AsRef<str>>(details: T) -> MazeError {
+	MazeError::new(MazeErrorKind::Config, details)
+}
+
+// The entries of a TOML document or a JSON object, in the order they are written.
+pub(crate) fn parse(text: &str) -> MazeResult<Vec<(String, ConfigValue)>> {
+	let mut entries = Vec::new();
+	match text.trim_start().starts_with('{') {
+		true => {
+			let object: serde_json::Value = serde_json::from_str(text).map_err(|e| config_error(e.to_string()).caused_by(e))?;
+			flatten_json("", &object, &mut entries)?;
+		},
+		false => {
+			let table: toml::Table = text.parse().map_err(|e: toml::de::Error| config_error(e.to_string()).caused_by(e))?;
+			flatten_toml("", &toml::Value::Table(table), &mut entries)?;
+		}
+	}
+	Ok(entries)
+}
+
+fn join_key(prefix: &str, key: &str) -> String {
+	match prefix.is_empty() {
+		true => key.to_string(),
+		false => format!("{}.{}", prefix, key)
+	}
+}
+
+fn flatten_toml(key: &str, value: &toml::Value, entries: &mut Vec<(String, ConfigValue)>) -> MazeResult<()> {
+	if let toml::Value::Table(table) = value {
+		for (name, value) in table {
+			flatten_toml(&join_key(key, name), value, entries)?;
+		}
+		return Ok(());
+	}
+	entries.push((key.to_string(), toml_value(key, value)?));
+	Ok(())
+}
+
+fn toml_value(key: &str, value: &toml::Value) -> MazeResult<ConfigValue> {
+	match value {
+		toml::Value::Integer(n) => Ok(ConfigValue::Number(*n as f64)),
+		toml::Value::Float(n) => Ok(ConfigValue::Number(*n)),
+		toml::Value::Boolean(b) => Ok(ConfigValue::Bool(*b)),
+		toml::Value::String(text) => Ok(ConfigValue::Text(text.clone())),
+		toml::Value::Array(list) => list.iter().map(|value| toml_value(key, value)).collect::<MazeResult<_>>().map(ConfigValue::List),
+		_ => Err(config_error(format!("Unsupported value for '{}'", key)))
+	}
+}
+
+fn flatten_json(key: &str, value: &serde_json::Value, entries: &mut Vec<(String, ConfigValue)>) -> MazeResult<()> {
+	if let serde_json::Value::Object(object) = value {
+		for (name, value) in object {
+			flatten_json(&join_key(key, name), value, entries)?;
+		}
+		return Ok(());
+	}
+	entries.push((key.to_string(), json_value(key, value)?));
+	Ok(())
+}
+
+fn json_value(key: &str, value: &serde_json::Value) -> MazeResult<ConfigValue> {
+	match value {
+		serde_json::Value::Number(n) => n.as_f64().map(ConfigValue::Number).ok_or_else(|| config_error(format!("Invalid number for '{}'", key))),
+		serde_json::Value::Bool(b) => Ok(ConfigValue::Bool(*b)),
+		serde_json::Value::String(text) => Ok(ConfigValue::Text(text.clone())),
+		serde_json::Value::Array(list) => list.iter().map(|value| json_value(key, value)).collect::<MazeResult<_>>().map(ConfigValue::List),
+		_ => Err(config_error(format!("Unsupported value for '{}'", key)))
+	}
+}
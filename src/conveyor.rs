@@ -0,0 +1,70 @@
+// One-way tiles like conveyor belts and escalators. A tile of one of the palette's conveyor
+// colours points up, right, down or left: it can only be left in that direction and can't be
+// entered against it, so a belt is ridden one way only. Stairs and portals ignore conveyors.
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::{Maze, MazeError, MazeResult};
+use super::config::config_error;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+	Up,
+	Right,
+	Down,
+	Left,
+}
+
+impl Direction {
+	// in the order of Palette.conveyors
+	pub const ALL: [Direction; 4] = [Direction::Up, Direction::Right, Direction::Down, Direction::Left];
+
+	// (row, col) offset of a step in this direction
+	pub fn step(self) -> (i32, i32) {
+		match self {
+			Direction::Up => (-1, 0),
+			Direction::Right => (0, 1),
+			Direction::Down => (1, 0),
+			Direction::Left => (0, -1),
+		}
+	}
+}
+
+impl FromStr for Direction {
+	type Err = MazeError;
+
+	fn from_str(text: &str) -> MazeResult<Direction> {
+		match text.trim().to_ascii_lowercase().as_str() {
+			"up" => Ok(Direction::Up),
+			"right" => Ok(Direction::Right),
+			"down" => Ok(Direction::Down),
+			"left" => Ok(Direction::Left),
+			_ => Err(config_error(format!("Expected up, right, down or left, found '{}'", text)))
+		}
+	}
+}
+
+impl fmt::Display for Direction {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Direction::Up => write!(fmt, "up"),
+			Direction::Right => write!(fmt, "right"),
+			Direction::Down => write!(fmt, "down"),
+			Direction::Left => write!(fmt, "left"),
+		}
+	}
+}
+
+impl Maze {
+	// Whether the step (row, col) away from the pixel at from to the one at to goes along the
+	// conveyors on both of them. Diagonal steps can enter a conveyor from its sides, but can't leave it.
+	pub(crate) fn conveyor_allows(&self, from: usize, to: usize, (dr, dc): (i32, i32)) -> bool {
+		let leaves = self.palette.conveyor(&self.colors[from]).is_none_or(|direction| direction.step() == (dr, dc));
+		let enters = self.palette.conveyor(&self.colors[to]).is_none_or(|direction| {
+			let (along_r, along_c) = direction.step();
+			along_r * dr + along_c * dc >= 0
+		});
+		leaves && enters
+	}
+}
@@ -0,0 +1,210 @@
+// What a step of the path costs. The search asks a cost model for the cost of every step
+// between two neighbouring cells, given the keys held at the time. Stairs, portals and
+// waiting keep their own costs, and walls are never entered whatever the model says.
+// With diagonal moves the cost of a step is scaled like a weight, see Connectivity::step_cost.
+//
+// The cost table of TableCost is read from a side-car file keyed by colour:
+//
+//     "#7f7f7f" = 5
+//     "#22b14c" = 20
+//     default = 1    # every other colour, leave it out to make them impassable
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::sync::Arc;
+
+use super::{grey_level, Color, Coord, Maze, MazeError, MazeResult, PixelType};
+use super::config::{self, config_error};
+
+// a cell at one end of a step, as the cost model sees it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cell {
+	pub coord: Coord,
+	pub color: Color,
+	// a plain grey pixel, not a wall and not part of any marked area
+	pub free: bool,
+	// the (row, col) step the path came onto the cell with. None on the start, after stairs,
+	// portals and waiting, and on the cell stepped from unless the model uses_heading.
+	pub heading: Option<(i32, i32)>,
+}
+
+pub trait CostModel: fmt::Debug + Send + Sync {
+	// cost of the step from one cell to its neighbour with these keys held, None if it can't be taken
+	fn cost(&self, from: &Cell, to: &Cell, keys: &[Color]) -> Option<u32>;
+
+	// A lower bound of the cost of any step onto the cell, whatever it is taken from and with.
+	// A* multiplies the smallest one in the maze by the steps left, 0 turns it into Dijkstra.
+	fn min_cost(&self, _to: &Cell) -> u32 {
+		0
+	}
+
+	// whether the cost depends on the heading of the cell stepped from, the search then keeps
+	// a layer of distances for every heading
+	fn uses_heading(&self) -> bool {
+		false
+	}
+}
+
+// The weight of a grey pixel is its grey level, every other cell costs 1.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GreyCost;
+
+impl CostModel for GreyCost {
+	fn cost(&self, _from: &Cell, to: &Cell, _keys: &[Color]) -> Option<u32> {
+		Some(self.min_cost(to))
+	}
+
+	fn min_cost(&self, to: &Cell) -> u32 {
+		match to.free {
+			true => grey_level(&to.color) as u32,
+			false => 1
+		}
+	}
+}
+
+// every step costs the same
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UniformCost(pub u32);
+
+impl CostModel for UniformCost {
+	fn cost(&self, _from: &Cell, _to: &Cell, _keys: &[Color]) -> Option<u32> {
+		Some(self.0)
+	}
+
+	fn min_cost(&self, _to: &Cell) -> u32 {
+		self.0
+	}
+}
+
+// The perceived brightness (Rec. 601 luma) of any colour, at least 1. Inverted, dark cells cost more.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LuminanceCost {
+	pub inverted: bool,
+}
+
+impl CostModel for LuminanceCost {
+	fn cost(&self, _from: &Cell, to: &Cell, _keys: &[Color]) -> Option<u32> {
+		Some(self.min_cost(to))
+	}
+
+	fn min_cost(&self, to: &Cell) -> u32 {
+		let Color { r, g, b } = to.color;
+		let luma = (299 * r as u32 + 587 * g as u32 + 114 * b as u32) / 1000;
+		match self.inverted {
+			true => 255 - luma,
+			false => luma
+		}.max(1)
+	}
+}
+
+// the cost of stepping onto a colour is looked up in a table
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TableCost {
+	pub costs: HashMap<Color, u32>,
+	// cost of the colours missing from the table, None if they can't be entered
+	pub default: Option<u32>,
+}
+
+impl TableCost {
+	// Reads the table of a side-car file, see the top of this file.
+	pub fn load(file_name: &str) -> MazeResult<TableCost> {
+		let text = fs::read_to_string(file_name).map_err(|e| MazeError::from(e).context(format!("Failed to read {}", file_name)))?;
+
+		TableCost::parse(&text)
+	}
+
+	// the table of a TOML or JSON text
+	pub fn parse(text: &str) -> MazeResult<TableCost> {
+		let mut table = TableCost::default();
+		for (key, value) in config::parse(text)? {
+			let key = key.strip_prefix("cost.").unwrap_or(&key);
+			let cost = value.as_u32().ok_or_else(|| config_error(
+				format!("Expected a whole number for the cost of '{}'", key)
+			))?;
+			match key {
+				"default" => table.default = Some(cost),
+				_ => { table.costs.insert(key.parse()?, cost); }
+			}
+		}
+		Ok(table)
+	}
+}
+
+impl CostModel for TableCost {
+	fn cost(&self, _from: &Cell, to: &Cell, _keys: &[Color]) -> Option<u32> {
+		self.costs.get(&to.color).copied().or(self.default)
+	}
+
+	fn min_cost(&self, to: &Cell) -> u32 {
+		// в непроходима клетка не се стъпва, така че тя не сваля оценката
+		self.cost(to, to, &[]).unwrap_or(u32::MAX)
+	}
+}
+
+// the cost of another model, plus a penalty for every step that changes the heading
+#[derive(Clone, Debug)]
+pub struct TurnPenalty {
+	pub base: Arc<dyn CostModel>,
+	pub penalty: u32,
+}
+
+impl CostModel for TurnPenalty {
+	fn cost(&self, from: &Cell, to: &Cell, keys: &[Color]) -> Option<u32> {
+		let cost = self.base.cost(from, to, keys)?;
+		match (from.heading, to.heading) {
+			(Some(heading), Some(next)) if heading != next => Some(cost.saturating_add(self.penalty)),
+			_ => Some(cost)
+		}
+	}
+
+	fn min_cost(&self, to: &Cell) -> u32 {
+		self.base.min_cost(to)
+	}
+
+	fn uses_heading(&self) -> bool {
+		true
+	}
+}
+
+// "grey", "uniform[:<cost>]" (default 1), "luminance", "luminance-inverted" or "table:<file>"
+pub fn parse_cost_model(text: &str) -> MazeResult<Arc<dyn CostModel>> {
+	let (name, arg) = match text.split_once(':') {
+		Some((name, arg)) => (name, Some(arg)),
+		None => (text, None)
+	};
+
+	match (name.trim().to_ascii_lowercase().as_str(), arg) {
+		("grey", None) => Ok(Arc::new(GreyCost)),
+		("uniform", None) => Ok(Arc::new(UniformCost(1))),
+		("uniform", Some(cost)) => match cost.trim().parse() {
+			Ok(cost) => Ok(Arc::new(UniformCost(cost))),
+			Err(_) => Err(config_error(format!("Expected a number for the uniform cost, found '{}'", cost)))
+		},
+		("luminance", None) => Ok(Arc::new(LuminanceCost { inverted: false })),
+		("luminance-inverted", None) => Ok(Arc::new(LuminanceCost { inverted: true })),
+		("table", Some(file_name)) => Ok(Arc::new(TableCost::load(file_name)?)),
+		_ => Err(config_error(format!("Expected grey, uniform[:cost], luminance, luminance-inverted or table:<file> costs, found '{}'", text)))
+	}
+}
+
+impl Maze {
+	// the cost model used by the next search, GreyCost by default
+	pub fn set_cost_model(&mut self, cost_model: Arc<dyn CostModel>) {
+		self.cost_model = cost_model;
+	}
+
+	pub fn cost_model(&self) -> &dyn CostModel {
+		self.cost_model.as_ref()
+	}
+
+	// the cell at c, whose pixel is at indx, as passed to the cost model
+	pub(crate) fn cost_cell(&self, c: &Coord, indx: usize, heading: Option<(i32, i32)>) -> Cell {
+		let color = self.colors[indx];
+		let free = match self.p_types[indx] {
+			PixelType::UNSET => self.palette.classify(&color) == PixelType::FREE,
+			p_type => p_type == PixelType::FREE
+		};
+		Cell { coord: *c, color, free, heading }
+	}
+}
@@ -0,0 +1,186 @@
+// Why a search didn't reach the end, for the people who draw the mazes.
+
+use super::{Color, Coord, Maze, MazeError, MazeErrorKind, MazeResult, PixelType, MAX_DIST, NO_REGION};
+use super::format::{self, RawImage};
+use super::ImageFormat;
+
+// tint of the explored pixels in save_diagnosis
+const EXPLORED_COLOR: Color = Color { r: 0, g: 162, b: 232 };
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NoPathReason {
+	// there is no END pixel in the image
+	NoEnd,
+	// no door stopped the search, the END is cut off by walls
+	WalledOff,
+	// a door the search ran into has no key of its colour anywhere in the maze
+	DoorWithoutKey,
+	// all doors the search ran into have keys, but the keys can't be reached either
+	KeyOutOfReach,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnosis {
+	pub reason: NoPathReason,
+	// colours of the doors at the edge of the explored area
+	pub blocking_doors: Vec<Color>,
+	// the blocking doors that have no key anywhere in the image
+	pub doors_without_key: Vec<Color>,
+	// bounding box of the pixels reached from the start, (top left, bottom right)
+	pub reachable: (Coord, Coord),
+	// pixels reached from the start with any combination of keys, in row-major order
+	pub explored: Vec<bool>,
+}
+
+impl Maze {
+	// Explains the last failed search. Returns None if it found the end or
+	// if it never started (e.g. there is no start).
+	pub fn diagnose(&mut self) -> Option<Diagnosis> {
+		if self.end.is_some() || self.dists.is_empty() {
+			return None;
+		}
+
+		// класифицираме и пикселите, до които търсенето не е стигнало, за да намерим всички ключове
+		self.classify_all();
+
+		let explored: Vec<bool> = (0..self.colors.len())
+			.map(|indx| self.dists.iter().any(|layer| layer[indx] != MAX_DIST))
+			.collect();
+
+		// без START няма и изследвана област
+		if !explored.contains(&true) {
+			return None;
+		}
+
+		let mut reachable: Option<(Coord, Coord)> = None;
+		for indx in explored.iter().enumerate().filter(|(_, reached)| **reached).map(|(indx, _)| indx) {
+			let c = self.coord_at(indx);
+			reachable = Some(match reachable {
+				Some((min, max)) => (
+					Coord::on_floor(min.row.min(c.row), min.col.min(c.col), min.floor.min(c.floor)),
+					Coord::on_floor(max.row.max(c.row), max.col.max(c.col), max.floor.max(c.floor)),
+				),
+				None => (c, c)
+			});
+		}
+
+		// вратата не е спряла търсенето, ако някой етаж е минал през нея (или е взел ключа на портала)
+		let passed = |color: &Color| {
+			let door_reached = explored.iter().enumerate().any(|(indx, reached)| {
+				*reached && self.p_types[indx] == PixelType::ZONE && self.region_ids[indx] != NO_REGION
+					&& self.palette.matches(color, &self.regions[self.region_ids[indx] as usize].color)
+			});
+			let portal_opened = self.portals.iter().any(|portal| portal.key == Some(*color))
+				&& self.held.iter().any(|held| held.iter().any(|key| self.palette.matches(key, color)));
+			door_reached || portal_opened
+		};
+		let blocking_doors: Vec<Color> = self.blocked_doors.iter()
+			.filter(|color| !passed(color))
+			.cloned()
+			.collect();
+
+		let has_key = |color: &Color| self.regions.iter()
+			.any(|region| region.p_type == PixelType::KEY && self.palette.matches(&region.color, color));
+		let doors_without_key: Vec<Color> = blocking_doors.iter()
+			.filter(|color| !has_key(color))
+			.cloned()
+			.collect();
+
+		let has_end = self.colors.iter().any(|color| self.palette.classify(color) == PixelType::END);
+		let reason = match (has_end, blocking_doors.is_empty(), doors_without_key.is_empty()) {
+			(false, _, _) => NoPathReason::NoEnd,
+			(true, true, _) => NoPathReason::WalledOff,
+			(true, false, false) => NoPathReason::DoorWithoutKey,
+			(true, false, true) => NoPathReason::KeyOutOfReach,
+		};
+
+		Some(Diagnosis {
+			reason,
+			blocking_doors,
+			doors_without_key,
+			reachable: reachable.unwrap_or_default(),
+			explored,
+		})
+	}
+
+	// The maze with the explored pixels tinted blue, so the blocking doors are the untinted
+	// colours at its edge. Text mazes can't be written this way, pick an image extension.
+	// Every floor goes to its own file like in save_path.
+	pub fn save_diagnosis(&self, diagnosis: &Diagnosis, file_name: &str) -> MazeResult<()> {
+		let mut floors: Vec<RawImage> = self.images.clone();
+		let blend = |a: u8, b: u8| ((a as u32 + b as u32) / 2) as u8;
+		for indx in (0..diagnosis.explored.len()).filter(|indx| diagnosis.explored[*indx]) {
+			let c = self.coord_at(indx);
+			let img = &mut floors[c.floor as usize];
+			for (x, y) in self.topology.pixels(&c, img.width, img.height) {
+				let color = &mut img.colors[(y * img.width + x) as usize];
+				*color = Color {
+					r: blend(color.r, EXPLORED_COLOR.r),
+					g: blend(color.g, EXPLORED_COLOR.g),
+					b: blend(color.b, EXPLORED_COLOR.b),
+				};
+			}
+		}
+
+		let format = ImageFormat::from_extension(file_name).unwrap_or(self.format);
+		for (floor, img) in floors.iter().enumerate() {
+			format::write_bytes(&format::encode(img, format)?, &self.floor_file_name(file_name, floor as u32))?;
+		}
+		Ok(())
+	}
+
+	// The error of a search that ran out of states, with the reason from diagnose.
+	// A door without a key next to a key-sized area with missing pixels most likely
+	// means the key was drawn wrong, so that is reported as a MalformedKey.
+	pub(crate) fn unreachable_error(&mut self) -> MazeError {
+		let diagnosis = match self.diagnose() {
+			Some(diagnosis) => diagnosis,
+			None => return MazeError::new(
+				MazeErrorKind::InternalInvariant,
+				"The search ran out of states, but there is nothing to diagnose.",
+			)
+		};
+
+		let colors = |colors: &[Color]| colors.iter().map(|color| color.to_string()).collect::<Vec<String>>().join(", ");
+		match diagnosis.reason {
+			NoPathReason::NoEnd => MazeError::new(
+				MazeErrorKind::NoEnd,
+				"There is no end zone.",
+			),
+			NoPathReason::WalledOff => MazeError::new(
+				MazeErrorKind::Unreachable,
+				"The end can't be reached from the start.",
+			),
+			NoPathReason::DoorWithoutKey => {
+				let key_area = self.key_height * self.key_width;
+				let malformed = self.regions.iter().filter(|region| {
+					self.marked_keys.is_none()
+						&& region.p_type == PixelType::ZONE
+						&& region.max.row - region.min.row + 1 == self.key_height
+						&& region.max.col - region.min.col + 1 == self.key_width
+						&& region.size < key_area
+				});
+				// за предпочитане е ключ със същия цвят като вратата
+				let malformed = malformed.clone()
+					.find(|region| diagnosis.doors_without_key.iter().any(|door| self.palette.matches(door, &region.color)))
+					.or_else(|| malformed.clone().next());
+
+				match malformed {
+					Some(region) => MazeError::new(
+						MazeErrorKind::MalformedKey { color: region.color, bbox: (region.min, region.max) },
+						format!("The end can't be reached. The {} area is as big as a key, but has only {} of its {} pixels.",
+							region.color, region.size, key_area),
+					).at(region.min),
+					None => MazeError::new(
+						MazeErrorKind::Unreachable,
+						format!("The end can't be reached, there is no key for the doors {}.", colors(&diagnosis.doors_without_key)),
+					)
+				}
+			},
+			NoPathReason::KeyOutOfReach => MazeError::new(
+				MazeErrorKind::Unreachable,
+				format!("The end can't be reached, the keys for the doors {} are out of reach.", colors(&diagnosis.blocking_doors)),
+			)
+		}
+	}
+}
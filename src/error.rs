@@ -1,60 +1,142 @@
 use std::convert::{From, AsRef};
 use std::error::Error;
 use std::fmt;
+use std::io;
 
+use super::{Color, Coord};
 use self::MazeErrorKind::*;
 
 pub type MazeResult<T> = Result<T, MazeError>;
 
-#[derive(Debug, Eq, PartialEq)]
+// the error that caused a MazeError, e.g. the io::Error of a failed read or the PNG decoder error
+type Source = Box<dyn Error + Send + Sync + 'static>;
+
+#[derive(Debug)]
 pub struct MazeError {
     pub kind: MazeErrorKind,
     pub details: String,
+    // pixel the error is about, if there is one
+    pub coord: Option<Coord>,
+    source: Option<Source>,
 }
 
 impl MazeError {
     pub fn new<T: AsRef<str>>(kind: MazeErrorKind, details: T) -> MazeError {
         MazeError {
-            kind: kind,
+            kind,
             details: String::from(details.as_ref()),
+            coord: None,
+            source: None,
         }
     }
+
+	pub fn at(mut self, coord: Coord) -> MazeError {
+		self.coord = Some(coord);
+		self
+	}
+
+	pub fn caused_by<E: Into<Source>>(mut self, source: E) -> MazeError {
+		self.source = Some(source.into());
+		self
+	}
+
+	// the same error with details prefixed by context, e.g. the name of the file
+	pub fn context<T: AsRef<str>>(mut self, context: T) -> MazeError {
+		self.details = format!("{}: {}", context.as_ref(), self.details);
+		self
+	}
 }
 
+// the source is left out, two errors are equal if they say the same about the same pixel
+impl PartialEq for MazeError {
+	fn eq(&self, other: &Self) -> bool {
+		self.kind == other.kind && self.details == other.details && self.coord == other.coord
+	}
+}
+
+impl Eq for MazeError {}
+
 impl fmt::Display for MazeError {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
 		let kind_desc: &str = self.kind.as_ref();
-		write!(fmt, "{}: {}", kind_desc, self.to_string())
+		write!(fmt, "{}: {}", kind_desc, self.details)?;
+		if let Some(coord) = &self.coord {
+			match coord.floor {
+				0 => write!(fmt, " (row {}, col {})", coord.row, coord.col)?,
+				floor => write!(fmt, " (row {}, col {}, floor {})", coord.row, coord.col, floor)?
+			}
+		}
+		Ok(())
     }
 }
 
-/*
 impl From<io::Error> for MazeError {
     fn from(err: io::Error) -> MazeError {
-        MazeError::new(MazeIoError(err), "Io Error")
+		let details = err.to_string();
+        MazeError::new(Io(err), details)
     }
-}*/
+}
 
 impl Error for MazeError {
-    fn description(&self) -> &str {
-        &self.details
-    }
+	fn source(&self) -> Option<&(dyn Error + 'static)> {
+		match (&self.source, &self.kind) {
+			(Some(source), _) => Some(source.as_ref()),
+			(None, Io(err)) => Some(err),
+			_ => None
+		}
+	}
 }
 
-#[derive(Debug, Eq, PartialEq)] // #[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Debug)]
 pub enum MazeErrorKind {
     CoordOutOfRange,
 	NoStart,
+	// there is no END pixel at all
 	NoEnd,
+	// START pixels in more than one area where a single start is needed,
+	// coord is the first pixel of the second one
+	MultipleStarts,
+	// there is an END, but no path leads to it
+	Unreachable,
+	// a key area of the right size that isn't a full rectangle, bbox is (top left, bottom right)
+	MalformedKey { color: Color, bbox: (Coord, Coord) },
+	// key size detection found several equally likely sizes
+	AmbiguousKeySize,
+	Config,
+	Io(io::Error),
+	Decode,
+	// a bug - the search left the maze in a state it should never be in
+	InternalInvariant,
 	Other
 }
 
+// io errors are compared by their io::ErrorKind only
+impl PartialEq for MazeErrorKind {
+	fn eq(&self, other: &Self) -> bool {
+		match (self, other) {
+			(Io(err), Io(other_err)) => err.kind() == other_err.kind(),
+			(MalformedKey { color, bbox }, MalformedKey { color: other_color, bbox: other_bbox }) => color == other_color && bbox == other_bbox,
+			_ => std::mem::discriminant(self) == std::mem::discriminant(other)
+		}
+	}
+}
+
+impl Eq for MazeErrorKind {}
+
 impl AsRef<str> for MazeErrorKind {
     fn as_ref(&self) -> &str {
         match self {
             CoordOutOfRange => "Coords out of range",
 			NoStart => "No start key",
+			MultipleStarts => "Multiple starts",
+			Unreachable => "Unreachable end",
+			MalformedKey { .. } => "Malformed key",
+			AmbiguousKeySize => "Ambiguous key size",
+			Config => "Invalid config",
+			Io(_) => "I/O error",
+			Decode => "Invalid image",
+			InternalInvariant => "Internal error",
             _ => "Maze Error",
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,80 @@
+// Mazes of several floors, one image per floor. A cell of the stair colour leads to the
+// cell at the same row and column on the floor above and below, if that one is a stair
+// too. Keys picked up on one floor open the doors of the same colour on every floor.
+
+use std::path::Path;
+
+use super::{Coord, Maze, MazeResult, PixelType, Topology};
+use super::config::config_error;
+
+// the same as stepping onto a key or a door
+pub(crate) const DEFAULT_STAIR_COST: u32 = 1;
+
+impl Maze {
+	// Stacks mazes into one with a floor for each of them, in the given order from floor 0.
+	// The floors need the same size and can't mix text and images, the palette and the format
+	// are the ones of the first floor. The cells are the pixels again, set the topology after
+	// stacking.
+	pub fn stack(floors: Vec<Maze>) -> MazeResult<Maze> {
+		let mut floors = floors.into_iter();
+		let mut maze = floors.next().ok_or_else(|| config_error("There are no floors to stack."))?;
+
+		for floor in floors {
+			let (first, img) = (&maze.images[0], &floor.images[0]);
+			if (img.width, img.height) != (first.width, first.height) {
+				return Err(config_error(format!("Floor {} is {}x{}, but floor 0 is {}x{}.",
+					maze.images.len(), img.width, img.height, first.width, first.height)));
+			}
+			match (&mut maze.marked_keys, floor.marked_keys) {
+				(Some(marks), Some(floor_marks)) => marks.extend(floor_marks),
+				(None, None) => {},
+				_ => return Err(config_error("A maze can't have both text and image floors."))
+			}
+			maze.images.extend(floor.images);
+		}
+
+		maze.set_topology(Topology::default())?;
+		Ok(maze)
+	}
+
+	pub fn floors(&self) -> u32 {
+		self.images.len() as u32
+	}
+
+	pub fn stair_cost(&self) -> u32 {
+		self.stair_cost
+	}
+
+	// used by the next search, scaled like the weight of a straight step
+	pub fn set_stair_cost(&mut self, stair_cost: u32) {
+		self.stair_cost = stair_cost;
+	}
+
+	// The file a floor is written to: the name itself for a single floor, otherwise
+	// the floor number goes before the extension, e.g. "route.floor1.png".
+	pub fn floor_file_name(&self, file_name: &str, floor: u32) -> String {
+		if self.images.len() < 2 {
+			return file_name.to_string();
+		}
+
+		let path = Path::new(file_name);
+		match (path.file_stem(), path.extension()) {
+			(Some(stem), Some(extension)) => path
+				.with_file_name(format!("{}.floor{}.{}", stem.to_string_lossy(), floor, extension.to_string_lossy()))
+				.to_string_lossy()
+				.into_owned(),
+			_ => format!("{}.floor{}", file_name, floor)
+		}
+	}
+
+	// the cells on the floors below and above a stair, None for cells that aren't stairs
+	pub(crate) fn stairs_from(&self, c: &Coord) -> [Option<Coord>; 2] {
+		match self.pixel_indx(c).map(|indx| self.p_types[indx]) {
+			Ok(PixelType::STAIR) => [
+				c.floor.checked_sub(1).map(|floor| Coord { floor, ..*c }),
+				Some(Coord { floor: c.floor + 1, ..*c }),
+			],
+			_ => [None, None]
+		}
+	}
+}
@@ -0,0 +1,355 @@
+// Reading and writing the image formats mazes are drawn in.
+
+use std::fs;
+use std::io::Cursor;
+use std::num::NonZeroU64;
+use std::path::Path;
+
+use super::{Color, MazeError, MazeErrorKind, MazeResult};
+use super::text;
+
+// most bytes of a decoded GIF frame, 4 per pixel; the canvas is held to the same number of pixels
+const GIF_MEMORY_LIMIT: u64 = 50_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+	Bmp,
+	Png,
+	// P3 and P6
+	Ppm,
+	// P2 and P5
+	Pgm,
+	Gif,
+	// see text.rs
+	Text,
+}
+
+impl ImageFormat {
+	// recognizes the format by the first bytes of the file
+	pub fn detect(bytes: &[u8]) -> Option<ImageFormat> {
+		if bytes.starts_with(b"BM") { Some(ImageFormat::Bmp) }
+		else if bytes.starts_with(b"\x89PNG\r\n\x1a\n") { Some(ImageFormat::Png) }
+		else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") { Some(ImageFormat::Gif) }
+		else if bytes.starts_with(b"P3") || bytes.starts_with(b"P6") { Some(ImageFormat::Ppm) }
+		else if bytes.starts_with(b"P2") || bytes.starts_with(b"P5") { Some(ImageFormat::Pgm) }
+		else if text::is_text_maze(bytes) { Some(ImageFormat::Text) }
+		else { None }
+	}
+
+	pub fn from_extension(file_name: &str) -> Option<ImageFormat> {
+		let extension = Path::new(file_name).extension()?.to_str()?.to_ascii_lowercase();
+		match extension.as_str() {
+			"bmp" => Some(ImageFormat::Bmp),
+			"png" => Some(ImageFormat::Png),
+			"ppm" => Some(ImageFormat::Ppm),
+			"pgm" => Some(ImageFormat::Pgm),
+			"gif" => Some(ImageFormat::Gif),
+			"txt" | "maze" => Some(ImageFormat::Text),
+			_ => None
+		}
+	}
+}
+
+// decoded image, colours in row-major order
+#[derive(Clone, Default)]
+pub(crate) struct RawImage {
+	pub width: u32,
+	pub height: u32,
+	pub colors: Vec<Color>,
+}
+
+fn decode_error<T: AsRef<str>>(details: T) -> MazeError {
+	MazeError::new(MazeErrorKind::Decode, details)
+}
+
+// Number of pixels of a width x height image, checked against the most the data can hold
+// before anything of that size is allocated.
+fn pixel_count(width: u32, height: u32, limit: usize) -> MazeResult<usize> {
+	(width as usize).checked_mul(height as usize)
+		.filter(|count| *count <= limit)
+		.ok_or_else(|| decode_error(format!("the header claims {}x{} pixels, more than the data holds", width, height)))
+}
+
+pub(crate) fn read(file_name: &str) -> MazeResult<Vec<u8>> {
+	fs::read(file_name).map_err(|e| MazeError::from(e).context(format!("Failed to read {}", file_name)))
+}
+
+pub(crate) fn write_bytes(bytes: &[u8], file_name: &str) -> MazeResult<()> {
+	fs::write(file_name, bytes).map_err(|e| MazeError::from(e).context(format!("Failed to write {}", file_name)))
+}
+
+pub(crate) fn decode(bytes: &[u8]) -> MazeResult<(ImageFormat, RawImage)> {
+	let format = ImageFormat::detect(bytes).ok_or_else(|| decode_error("Unknown image format"))?;
+
+	let img = match format {
+		ImageFormat::Bmp => decode_bmp(bytes)?,
+		ImageFormat::Png => decode_png(bytes)?,
+		ImageFormat::Ppm | ImageFormat::Pgm => decode_pnm(bytes)?,
+		ImageFormat::Gif => decode_gif(bytes)?,
+		ImageFormat::Text => return Err(decode_error("Text mazes are read by Maze::parse_text")),
+	};
+
+	if (img.width as usize).checked_mul(img.height as usize) != Some(img.colors.len()) {
+		return Err(decode_error("Image data is shorter than its size"));
+	}
+
+	Ok((format, img))
+}
+
+pub(crate) fn encode(img: &RawImage, format: ImageFormat) -> MazeResult<Vec<u8>> {
+	match format {
+		ImageFormat::Bmp => encode_bmp(img),
+		ImageFormat::Png => encode_png(img),
+		ImageFormat::Ppm => Ok(encode_ppm(img)),
+		ImageFormat::Pgm => encode_pgm(img),
+		ImageFormat::Gif => encode_gif(img),
+		ImageFormat::Text => Err(decode_error("Text mazes are written by Maze::render_text")),
+	}
+}
+
+impl From<&bmp::Image> for RawImage {
+	fn from(item: &bmp::Image) -> Self {
+		let (width, height) = (item.get_width(), item.get_height());
+		let mut colors = Vec::with_capacity(width as usize * height as usize);
+		for row in 0..height {
+			for col in 0..width {
+				colors.push(Color::from(item.get_pixel(col, row)));
+			}
+		}
+
+		RawImage { width, height, colors }
+	}
+}
+
+fn decode_bmp(bytes: &[u8]) -> MazeResult<RawImage> {
+	// bmp заделя памет по размера от заглавието, така че той се проверява преди това;
+	// всеки пиксел заема поне един бит
+	let field = |at: usize| bytes.get(at..at + 4).map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]).unsigned_abs());
+	if let (Some(width), Some(height)) = (field(18), field(22)) {
+		pixel_count(width, height, bytes.len().saturating_mul(8))?;
+	}
+
+	let img = bmp::from_reader(&mut Cursor::new(bytes)).map_err(|e| decode_error(format!("Invalid BMP: {}", e)).caused_by(e))?;
+	Ok(RawImage::from(&img))
+}
+
+fn encode_bmp(img: &RawImage) -> MazeResult<Vec<u8>> {
+	let mut out = bmp::Image::new(img.width, img.height);
+	for (indx, color) in img.colors.iter().enumerate() {
+		out.set_pixel(indx as u32 % img.width, indx as u32 / img.width, (*color).into());
+	}
+
+	let mut bytes = Vec::new();
+	out.to_writer(&mut bytes).map_err(|e| decode_error(format!("Failed to encode BMP: {}", e)).caused_by(e))?;
+	Ok(bytes)
+}
+
+fn decode_png(bytes: &[u8]) -> MazeResult<RawImage> {
+	let png_error = |e: png::DecodingError| decode_error(format!("Invalid PNG: {}", e)).caused_by(e);
+
+	let mut decoder = png::Decoder::new(bytes);
+	// палитрата и по-малко от 8 бита се разгъват до 8 бита на канал
+	decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+	let mut reader = decoder.read_info().map_err(png_error)?;
+	let mut buf = vec![0; reader.output_buffer_size()];
+	let info = reader.next_frame(&mut buf).map_err(png_error)?;
+
+	let channels = match info.color_type {
+		png::ColorType::Grayscale => 1,
+		png::ColorType::GrayscaleAlpha => 2,
+		png::ColorType::Rgb => 3,
+		png::ColorType::Rgba => 4,
+		png::ColorType::Indexed => return Err(decode_error("Invalid PNG: palette was not expanded")),
+	};
+
+	let mut colors = Vec::with_capacity(info.width as usize * info.height as usize);
+	for line in buf.chunks(info.line_size).take(info.height as usize) {
+		for pxl in line.chunks(channels).take(info.width as usize) {
+			colors.push(match channels {
+				1 | 2 => Color { r: pxl[0], g: pxl[0], b: pxl[0] },
+				_ => Color { r: pxl[0], g: pxl[1], b: pxl[2] },
+			});
+		}
+	}
+
+	Ok(RawImage { width: info.width, height: info.height, colors })
+}
+
+fn encode_png(img: &RawImage) -> MazeResult<Vec<u8>> {
+	let png_error = |e: png::EncodingError| decode_error(format!("Failed to encode PNG: {}", e)).caused_by(e);
+
+	let mut bytes = Vec::new();
+	{
+		let mut encoder = png::Encoder::new(&mut bytes, img.width, img.height);
+		encoder.set_color(png::ColorType::Rgb);
+		encoder.set_depth(png::BitDepth::Eight);
+		let mut writer = encoder.write_header().map_err(png_error)?;
+
+		let data: Vec<u8> = img.colors.iter().flat_map(|c| [c.r, c.g, c.b]).collect();
+		writer.write_image_data(&data).map_err(png_error)?;
+		writer.finish().map_err(png_error)?;
+	}
+
+	Ok(bytes)
+}
+
+fn decode_gif(bytes: &[u8]) -> MazeResult<RawImage> {
+	let gif_error = |e: gif::DecodingError| decode_error(format!("Invalid GIF: {}", e)).caused_by(e);
+
+	let mut options = gif::DecodeOptions::new();
+	options.set_color_output(gif::ColorOutput::RGBA);
+	options.set_memory_limit(gif::MemoryLimit::Bytes(NonZeroU64::new(GIF_MEMORY_LIMIT).unwrap()));
+	let mut decoder = options.read_info(bytes).map_err(gif_error)?;
+	let (width, height) = (decoder.width() as u32, decoder.height() as u32);
+
+	// кадърът може да е по-малък от платното - останалото е черно
+	let count = pixel_count(width, height, (GIF_MEMORY_LIMIT / 4) as usize)?;
+	let mut colors = vec![Color { r: 0, g: 0, b: 0 }; count];
+	match decoder.read_next_frame().map_err(gif_error)? {
+		Some(frame) => {
+			for (indx, pxl) in frame.buffer.chunks(4).enumerate() {
+				let row = frame.top as u32 + indx as u32 / frame.width as u32;
+				let col = frame.left as u32 + indx as u32 % frame.width as u32;
+				if row < height && col < width {
+					colors[(row * width + col) as usize] = Color { r: pxl[0], g: pxl[1], b: pxl[2] };
+				}
+			}
+		},
+		None => return Err(decode_error("Invalid GIF: there are no frames"))
+	}
+
+	if decoder.next_frame_info().map_err(gif_error)?.is_some() {
+		return Err(decode_error("Animated GIFs are not supported"));
+	}
+
+	Ok(RawImage { width, height, colors })
+}
+
+fn encode_gif(img: &RawImage) -> MazeResult<Vec<u8>> {
+	let gif_error = |e: gif::EncodingError| decode_error(format!("Failed to encode GIF: {}", e)).caused_by(e);
+
+	if img.width > u16::MAX as u32 || img.height > u16::MAX as u32 {
+		return Err(decode_error("Image is too big for GIF"));
+	}
+
+	// GIF има палитра от най-много 256 цвята, а цветовете на лабиринта трябва да се запазят точно
+	let mut palette: Vec<Color> = Vec::new();
+	let mut pixels: Vec<u8> = Vec::with_capacity(img.colors.len());
+	for color in &img.colors {
+		let indx = match palette.iter().position(|c| c == color) {
+			Some(indx) => indx,
+			None if palette.len() < 256 => {
+				palette.push(*color);
+				palette.len() - 1
+			},
+			None => return Err(decode_error("Image has more than 256 colours and can't be saved as GIF")),
+		};
+		pixels.push(indx as u8);
+	}
+
+	let palette_bytes: Vec<u8> = palette.iter().flat_map(|c| [c.r, c.g, c.b]).collect();
+	let frame = gif::Frame::from_palette_pixels(img.width as u16, img.height as u16, pixels, palette_bytes, None);
+
+	let mut bytes = Vec::new();
+	{
+		let mut encoder = gif::Encoder::new(&mut bytes, img.width as u16, img.height as u16, &[]).map_err(gif_error)?;
+		encoder.write_frame(&frame).map_err(gif_error)?;
+	}
+
+	Ok(bytes)
+}
+
+// P2/P3 are whitespace separated numbers, P5/P6 have a text header followed by binary samples
+fn decode_pnm(bytes: &[u8]) -> MazeResult<RawImage> {
+	let magic = &bytes[..2];
+	let mut pos = 2;
+
+	let mut header = [0u32; 3];
+	for value in header.iter_mut() {
+		*value = pnm_number(bytes, &mut pos)?;
+	}
+	let [width, height, max_value] = header;
+
+	if max_value == 0 || max_value > u16::MAX as u32 {
+		return Err(decode_error(format!("Invalid PNM: max value {} out of range", max_value)));
+	}
+
+	let channels = match magic {
+		b"P3" | b"P6" => 3,
+		_ => 1,
+	};
+	let sample_size = if max_value > 255 { 2 } else { 1 };
+	// всяка стойност заема поне една цифра или sample_size байта
+	let left = match magic {
+		b"P2" | b"P3" => bytes.len().saturating_sub(pos),
+		_ => bytes.len().saturating_sub(pos) / sample_size
+	};
+	let samples = pixel_count(width, height, left / channels)? * channels;
+	let scale = |value: u32| -> MazeResult<u8> {
+		match value <= max_value {
+			true => Ok((value * 255 / max_value) as u8),
+			false => Err(decode_error(format!("Invalid PNM: sample {} is bigger than {}", value, max_value))),
+		}
+	};
+
+	let mut values: Vec<u8> = Vec::with_capacity(samples);
+	if magic == b"P2" || magic == b"P3" {
+		for _ in 0..samples {
+			values.push(scale(pnm_number(bytes, &mut pos)?)?);
+		}
+	}
+	else {
+		// точно един празен символ след заглавието
+		pos += 1;
+		let data = bytes.get(pos..pos + samples * sample_size)
+			.ok_or_else(|| decode_error("Invalid PNM: image data is too short"))?;
+		for sample in data.chunks(sample_size) {
+			let value = sample.iter().fold(0u32, |acc, b| acc << 8 | *b as u32);
+			values.push(scale(value)?);
+		}
+	}
+
+	let colors = values.chunks(channels).map(|pxl| match channels {
+		3 => Color { r: pxl[0], g: pxl[1], b: pxl[2] },
+		_ => Color { r: pxl[0], g: pxl[0], b: pxl[0] },
+	}).collect();
+
+	Ok(RawImage { width, height, colors })
+}
+
+// next ASCII number in a PNM header or P2/P3 body, skipping whitespace and # comments
+fn pnm_number(bytes: &[u8], pos: &mut usize) -> MazeResult<u32> {
+	loop {
+		match bytes.get(*pos) {
+			Some(b'#') => while bytes.get(*pos).is_some_and(|b| *b != b'\n') { *pos += 1; },
+			Some(b) if b.is_ascii_whitespace() => *pos += 1,
+			_ => break
+		}
+	}
+
+	let start = *pos;
+	while bytes.get(*pos).is_some_and(|b| b.is_ascii_digit()) {
+		*pos += 1;
+	}
+
+	std::str::from_utf8(&bytes[start..*pos]).ok()
+		.and_then(|text| text.parse().ok())
+		.ok_or_else(|| decode_error("Invalid PNM: expected a number"))
+}
+
+fn encode_ppm(img: &RawImage) -> Vec<u8> {
+	let mut bytes = format!("P6\n{} {}\n255\n", img.width, img.height).into_bytes();
+	bytes.extend(img.colors.iter().flat_map(|c| [c.r, c.g, c.b]));
+	bytes
+}
+
+// PGM only holds grey pixels, a coloured one (a key, the path, ...) has to be saved in another format
+fn encode_pgm(img: &RawImage) -> MazeResult<Vec<u8>> {
+	if let Some(color) = img.colors.iter().find(|c| c.r != c.g || c.g != c.b) {
+		return Err(decode_error(format!("PGM can't hold the colour {}, save the image as PPM or another format", color)));
+	}
+
+	let mut bytes = format!("P5\n{} {}\n255\n", img.width, img.height).into_bytes();
+	bytes.extend(img.colors.iter().map(|c| c.r));
+	Ok(bytes)
+}
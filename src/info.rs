@@ -0,0 +1,53 @@
+use super::{Color, ImageFormat, Maze, PixelType};
+
+// what a maze is made of, judged by the palette colours alone
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MazeInfo {
+	// size of one floor
+	pub width: u32,
+	pub height: u32,
+	pub floors: u32,
+	pub format: ImageFormat,
+	pub walls: u32,
+	pub free: u32,
+	pub start: u32,
+	pub end: u32,
+	// key and door pixels
+	pub colored: u32,
+	// distinct key/door colours, in the order they first appear
+	pub colors: Vec<Color>,
+}
+
+impl Maze {
+	pub fn info(&self) -> MazeInfo {
+		let mut info = MazeInfo {
+			width: self.width,
+			height: self.height,
+			floors: self.floors(),
+			format: self.format,
+			walls: 0,
+			free: 0,
+			start: 0,
+			end: 0,
+			colored: 0,
+			colors: Vec::new(),
+		};
+
+		for color in &self.colors {
+			match self.palette.classify(color) {
+				PixelType::WALL => info.walls += 1,
+				PixelType::FREE => info.free += 1,
+				PixelType::START => info.start += 1,
+				PixelType::END => info.end += 1,
+				_ => {
+					info.colored += 1;
+					if !info.colors.iter().any(|known| self.palette.matches(known, color)) {
+						info.colors.push(*color);
+					}
+				}
+			}
+		}
+
+		info
+	}
+}
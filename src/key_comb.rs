@@ -0,0 +1,106 @@
+// Set of collected keys, bit i stands for the key at position i in Maze.keys.
+// Up to 128 keys fit in a single u128. Bigger sets spill into words of 64 bits with
+// no trailing zero words, so every set has exactly one representation and equal sets
+// compare and hash equal whatever order the keys were added in.
+
+const SMALL_BITS: u32 = 128;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum KeyComb {
+	Small(u128),
+	// only used when a key at position 128 or above is held
+	Wide(Vec<u64>),
+}
+
+impl Default for KeyComb {
+	fn default() -> Self {
+		KeyComb::Small(0)
+	}
+}
+
+impl KeyComb {
+	pub(crate) fn has(&self, pos: u32) -> bool {
+		match self {
+			KeyComb::Small(mask) => pos < SMALL_BITS && mask & (1 << pos) != 0,
+			KeyComb::Wide(words) => words.get((pos / 64) as usize).is_some_and(|word| word & (1 << (pos % 64)) != 0),
+		}
+	}
+
+	// the same set with the key at pos added
+	pub(crate) fn with(&self, pos: u32) -> KeyComb {
+		match self {
+			KeyComb::Small(mask) if pos < SMALL_BITS => KeyComb::Small(mask | (1 << pos)),
+			_ => {
+				let mut words = self.words();
+				let word = (pos / 64) as usize;
+				if words.len() <= word {
+					words.resize(word + 1, 0);
+				}
+				words[word] |= 1 << (pos % 64);
+				KeyComb::from_words(words)
+			}
+		}
+	}
+
+	// positions of the held keys in increasing order
+	pub(crate) fn positions(&self) -> impl Iterator<Item = u32> + '_ {
+		let words = self.words();
+		(0..words.len() as u32 * 64).filter(move |pos| words[(pos / 64) as usize] & (1 << (pos % 64)) != 0)
+	}
+
+	pub(crate) fn len(&self) -> u32 {
+		match self {
+			KeyComb::Small(mask) => mask.count_ones(),
+			KeyComb::Wide(words) => words.iter().map(|word| word.count_ones()).sum(),
+		}
+	}
+
+	fn words(&self) -> Vec<u64> {
+		match self {
+			KeyComb::Small(mask) => vec![*mask as u64, (*mask >> 64) as u64],
+			KeyComb::Wide(words) => words.clone(),
+		}
+	}
+
+	// the canonical form of a set given as 64 bit words
+	fn from_words(mut words: Vec<u64>) -> KeyComb {
+		while words.last() == Some(&0) {
+			words.pop();
+		}
+
+		match words.len() {
+			0..=2 => KeyComb::Small(words.iter().rev().fold(0u128, |mask, word| (mask << 64) | *word as u128)),
+			_ => KeyComb::Wide(words)
+		}
+	}
+}
+
+// Everything the search carries besides the pixel: the keys held, the checkpoints
+// visited, by their number, the tick in the cycle of the schedules and, for cost models
+// that charge for turning, the step the pixel was entered with.
+// Every distinct one gets its own layer of distances.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub(crate) struct Progress {
+	pub(crate) keys: KeyComb,
+	pub(crate) visited: KeyComb,
+	pub(crate) tick: u32,
+	pub(crate) heading: Option<(i32, i32)>,
+}
+
+impl Progress {
+	pub(crate) fn with_key(&self, pos: u32) -> Progress {
+		Progress { keys: self.keys.with(pos), ..self.clone() }
+	}
+
+	pub(crate) fn with_visited(&self, checkpoint: u32) -> Progress {
+		Progress { visited: self.visited.with(checkpoint), ..self.clone() }
+	}
+
+	pub(crate) fn with_tick(&self, tick: u32) -> Progress {
+		Progress { tick, ..self.clone() }
+	}
+
+	pub(crate) fn with_heading(&self, heading: Option<(i32, i32)>) -> Progress {
+		Progress { heading, ..self.clone() }
+	}
+}
@@ -0,0 +1,156 @@
+// Guessing the size of the keys from the image, for callers that don't know it.
+// A key is a solid rectangle and somewhere else in the maze there is a door of its
+// colour that doesn't look like it, so the most common size of such rectangles is
+// most likely the key size. Single pixels and one pixel wide lines are left out, the
+// drawing programs leave plenty of those along the edges of the coloured areas.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use super::{Maze, MazeError, MazeErrorKind, MazeResult, PixelType, Region};
+use super::config::config_error;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeySize {
+	// every key is exactly height x width pixels
+	Fixed { height: u32, width: u32 },
+	// the most common size found by Maze::detect_key_size
+	Auto,
+	// keys of any size: the smallest solid rectangles of a colour that also has a door
+	Any,
+}
+
+impl Default for KeySize {
+	fn default() -> Self {
+		KeySize::Fixed { height: 20, width: 20 }
+	}
+}
+
+// "auto", "any" or "<width>x<height>"
+impl FromStr for KeySize {
+	type Err = MazeError;
+
+	fn from_str(text: &str) -> MazeResult<KeySize> {
+		match text.trim().to_ascii_lowercase().as_str() {
+			"auto" => Ok(KeySize::Auto),
+			"any" => Ok(KeySize::Any),
+			size => {
+				let parsed = size.split_once('x').and_then(|(width, height)| match (width.parse(), height.parse()) {
+					(Ok(width), Ok(height)) if width > 0 && height > 0 => Some(KeySize::Fixed { height, width }),
+					_ => None
+				});
+				parsed.ok_or_else(|| config_error(format!("Expected a key size as WxH, auto or any, found '{}'", text)))
+			}
+		}
+	}
+}
+
+impl fmt::Display for KeySize {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			KeySize::Fixed { height, width } => write!(fmt, "{}x{}", width, height),
+			KeySize::Auto => write!(fmt, "auto"),
+			KeySize::Any => write!(fmt, "any"),
+		}
+	}
+}
+
+// how many key-like rectangles of one size there are
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeySizeCandidate {
+	pub height: u32,
+	pub width: u32,
+	pub count: u32,
+}
+
+fn dims(region: &Region) -> (u32, u32) {
+	(region.max.row - region.min.row + 1, region.max.col - region.min.col + 1)
+}
+
+fn is_solid(region: &Region) -> bool {
+	let (height, width) = dims(region);
+	height > 1 && width > 1 && region.size == height * width
+}
+
+impl Maze {
+	// Ids of the regions that look like keys: for every colour, the smallest solid rectangles
+	// (at least 2x2), if there is another area of that colour with a different size (the door).
+	// Expects every pixel to be classified with no key size, so all coloured areas are ZONEs.
+	pub(crate) fn key_like_regions(&self) -> Vec<usize> {
+		let zones: Vec<(usize, &Region)> = self.regions.iter()
+			.enumerate()
+			.filter(|(_, region)| region.p_type == PixelType::ZONE)
+			.collect();
+
+		zones.iter()
+			.filter(|(_, region)| is_solid(region))
+			.filter(|(_, region)| {
+				let same_color: Vec<&Region> = zones.iter()
+					.map(|(_, other)| *other)
+					.filter(|other| self.palette.matches(&other.color, &region.color))
+					.collect();
+				let smallest = same_color.iter().filter(|other| is_solid(other)).map(|other| other.size).min();
+
+				smallest == Some(region.size) && same_color.iter().any(|other| dims(other) != dims(region))
+			})
+			.map(|(id, _)| *id)
+			.collect()
+	}
+
+	// Histogram of the sizes of the key-like areas, the most common first.
+	// Forgets the path found by the last search.
+	pub fn key_size_candidates(&mut self) -> Vec<KeySizeCandidate> {
+		self.key_height = 0;
+		self.key_width = 0;
+		self.reset_search();
+		self.classify_all();
+
+		let mut counts: HashMap<(u32, u32), u32> = HashMap::new();
+		for id in self.key_like_regions() {
+			*counts.entry(dims(&self.regions[id])).or_insert(0) += 1;
+		}
+
+		let mut candidates: Vec<KeySizeCandidate> = counts.into_iter()
+			.map(|((height, width), count)| KeySizeCandidate { height, width, count })
+			.collect();
+		candidates.sort_by(|a, b| b.count.cmp(&a.count).then((a.height, a.width).cmp(&(b.height, b.width))));
+		candidates
+	}
+
+	// The (height, width) of the keys, None if nothing in the maze looks like a key.
+	// Fails with AmbiguousKeySize if several sizes are equally common.
+	pub fn detect_key_size(&mut self) -> MazeResult<Option<(u32, u32)>> {
+		let candidates = self.key_size_candidates();
+		match candidates.as_slice() {
+			[] => Ok(None),
+			[first, second, ..] if first.count == second.count => {
+				let sizes: Vec<String> = candidates.iter()
+					.take_while(|candidate| candidate.count == first.count)
+					.map(|candidate| format!("{}x{}", candidate.width, candidate.height))
+					.collect();
+				Err(MazeError::new(
+					MazeErrorKind::AmbiguousKeySize,
+					format!("There are {} keys of each of the sizes {}, pass the key size explicitly.", first.count, sizes.join(", ")),
+				))
+			},
+			[first, ..] => Ok(Some((first.height, first.width)))
+		}
+	}
+
+	// turns the key-like regions into keys, for KeySize::Any
+	pub(crate) fn promote_key_like_regions(&mut self) {
+		self.classify_all();
+		let mut is_key: Vec<bool> = vec![false; self.regions.len()];
+		for id in self.key_like_regions() {
+			is_key[id] = true;
+			self.regions[id].p_type = PixelType::KEY;
+		}
+
+		for (p_type, region_id) in self.p_types.iter_mut().zip(&self.region_ids) {
+			if let Some(true) = is_key.get(*region_id as usize) {
+				*p_type = PixelType::KEY;
+			}
+		}
+	}
+}
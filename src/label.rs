@@ -0,0 +1,53 @@
+// Numbers drawn into the output images, like the order the doors were opened in.
+
+use super::{Color, Coord};
+use super::format::RawImage;
+
+const DIGIT_WIDTH: u32 = 3;
+const DIGIT_HEIGHT: u32 = 5;
+
+// a row of a digit is 3 bits, the highest one is the leftmost pixel
+const DIGITS: [[u8; DIGIT_HEIGHT as usize]; 10] = [
+	[0b111, 0b101, 0b101, 0b101, 0b111],
+	[0b010, 0b110, 0b010, 0b010, 0b111],
+	[0b111, 0b001, 0b111, 0b100, 0b111],
+	[0b111, 0b001, 0b111, 0b001, 0b111],
+	[0b101, 0b101, 0b111, 0b001, 0b001],
+	[0b111, 0b100, 0b111, 0b001, 0b111],
+	[0b111, 0b100, 0b111, 0b101, 0b111],
+	[0b111, 0b001, 0b010, 0b010, 0b010],
+	[0b111, 0b101, 0b111, 0b101, 0b111],
+	[0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+// Draws the number centred in the box (top left, bottom right), scaled up as much as it fits
+// with a pixel to spare on every side. Boxes too small even for the smallest size are left as they are.
+// Only the pixels of the mask are painted, so the number doesn't spill out of an area that isn't a rectangle.
+pub(crate) fn draw_number(img: &mut RawImage, number: usize, (min, max): (Coord, Coord), mask: &[bool], color: Color) {
+	let digits: Vec<usize> = number.to_string().bytes().map(|b| (b - b'0') as usize).collect();
+	let width = digits.len() as u32 * (DIGIT_WIDTH + 1) - 1;
+	let (box_height, box_width) = (max.row - min.row + 1, max.col - min.col + 1);
+	let scale = (box_width.saturating_sub(2) / width).min(box_height.saturating_sub(2) / DIGIT_HEIGHT);
+	if scale == 0 {
+		return;
+	}
+
+	let top = min.row + (box_height - DIGIT_HEIGHT * scale) / 2;
+	let left = min.col + (box_width - width * scale) / 2;
+	for (i, digit) in digits.iter().enumerate() {
+		for (row, bits) in DIGITS[*digit].iter().enumerate() {
+			for col in (0..DIGIT_WIDTH).filter(|col| bits & (1 << (DIGIT_WIDTH - 1 - col)) != 0) {
+				let x = left + (i as u32 * (DIGIT_WIDTH + 1) + col) * scale;
+				let y = top + row as u32 * scale;
+				for dy in 0..scale {
+					for dx in 0..scale {
+						let indx = ((y + dy) * img.width + x + dx) as usize;
+						if mask[indx] {
+							img.colors[indx] = color;
+						}
+					}
+				}
+			}
+		}
+	}
+}
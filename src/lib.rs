@@ -6,82 +6,108 @@
 
 // external crates
 extern crate bmp;
-#[macro_use]
-extern crate lazy_static;
-extern crate bit_vec;
+extern crate rayon;
+extern crate glob;
 
 // used dependences
-use bit_vec::BitVec;
-use std::collections::{HashMap, HashSet, VecDeque};
-use std::rc::Rc;
-use std::cell::{RefCell};
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::cmp::Ordering;
 use std::hash::{Hash};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
 
 // self modules
 pub use error::{MazeError, MazeErrorKind, MazeResult};
+pub use solution::{Solution, Step};
+pub use palette::{Palette, Tolerance};
+pub use format::ImageFormat;
+pub use info::MazeInfo;
+pub use diagnosis::{Diagnosis, NoPathReason};
+pub use lint::{Lint, LintKind};
+pub use key_size::{KeySize, KeySizeCandidate};
+pub use rules::{Connectivity, KeyRules};
+pub use topology::{CellShape, Topology};
+use floors::DEFAULT_STAIR_COST;
+pub use portal::Portal;
+pub use conveyor::Direction;
+pub use schedule::Schedule;
+use schedule::WAIT_COST;
+pub use cost::{parse_cost_model, Cell, CostModel, GreyCost, LuminanceCost, TableCost, TurnPenalty, UniformCost};
+use rules::STRAIGHT_COST;
+pub use batch::{Batch, BatchEntry, BatchStatus, list_inputs, report_csv, report_json};
+use key_comb::{KeyComb, Progress};
+use format::RawImage;
+use config::config_error;
 
 
 // consts
 const MAX_DIST: u32 = u32::MAX;
 // const key_width: u32 = 20;
 // const key_height: u32 = 20;
-	
+
+// default palette
 const WALL_COLOR: Color = Color{ r: 0, g: 0, b: 0 };
 const START_COLOR: Color = Color{ r: 195, g: 195, b: 196 };
 const END_COLOR: Color = Color{ r: 126, g: 127, b: 127 };
 const PATH_COLOR: Color = Color{ r: 255, g: 0, b: 0 };
-lazy_static! {
-	static ref START_KEY_COMB: KeyComb = KeyComb::from_bytes(&[0b00000000]);
-	static ref DIRS: [Coord; 4] = [Coord::new(u32::MAX, 0), Coord::new(0, u32::MAX), Coord::new(0, 1), Coord::new(1, 0)];
-}
+// stairs of text mazes, images have none unless the palette gives a stair colour
+const STAIR_COLOR: Color = Color{ r: 185, g: 122, b: 87 };
+const WAIT_COLOR: Color = Color{ r: 255, g: 201, b: 14 };
+// id of the empty key combination in Maze.key_combs
+const START_KEY_COMB_ID: usize = 0;
+// Up to this many checkpoints the search tries every order they can be visited in,
+// which takes a layer of distances for every subset of them. With more it visits
+// them in the order of checkpoint_order.
+const EXACT_CHECKPOINTS: usize = 8;
 
-type KeyComb = BitVec;
-type KeyCombRef = Rc<KeyComb>; // only rc because I dont need internal mutability
-type PixelRef = Rc<RefCell<Pixel>>;
+// grey value of a (nearly) grey pixel
+fn grey_level(color: &Color) -> u8 {
+	((color.r as u32 + color.g as u32 + color.b as u32) / 3) as u8
+}
 
-fn is_grey(color: &Color) -> bool {
-	color.r == color.g && color.g == color.b
+#[derive(Hash, Eq, PartialEq, Clone, Copy, Debug)]
+pub struct Color {
+	pub r: u8,
+	pub g: u8,
+	pub b: u8
 }
 
-fn keycomb_set(key_comb: &KeyComb, pos: u32) -> KeyComb {
-	let mut tmp = key_comb.clone();
-	if (pos as usize) >= tmp.len() {
-		tmp.grow(pos as usize - tmp.len() + 1, false);
+impl Color {
+	// the biggest difference between two channels of the colours
+	pub fn distance(&self, other: &Color) -> u8 {
+		self.r.abs_diff(other.r)
+			.max(self.g.abs_diff(other.g))
+			.max(self.b.abs_diff(other.b))
 	}
-	tmp.set(pos as usize, true);
-	tmp
 }
 
-fn keycomb_unset(key_comb: &KeyComb, pos: u32) -> KeyComb {
-	let mut tmp = key_comb.clone();
-	if (pos as usize) < tmp.len() {
-		tmp.set(pos as usize, false);
+// "#rrggbb"
+impl fmt::Display for Color {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		write!(fmt, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
 	}
-	tmp
 }
 
-fn keycomb_eq(key_comb1: &KeyComb, key_comb2: &KeyComb) -> bool {
-	match key_comb1.len() < key_comb2.len() {
-		true => {
-			let mut tmp = key_comb1.clone();
-			tmp.grow(key_comb2.len() - key_comb1.len(), false);
-			
-			return tmp == *key_comb2;
-		}
-		false => {
-			let mut tmp = key_comb2.clone();
-			tmp.grow(key_comb1.len() - key_comb2.len(), false);
-			
-			return tmp == *key_comb1;
-		}
-	};
-}
+// "#rrggbb", "rrggbb" or "r,g,b"
+impl FromStr for Color {
+	type Err = MazeError;
 
-#[derive(Hash, Eq, PartialEq, Clone, Copy, Debug)]
-struct Color {
-	r: u8,
-	g: u8,
-	b: u8
+	fn from_str(text: &str) -> MazeResult<Color> {
+		let channels: Vec<&str> = text.split(',').map(|channel| channel.trim()).collect();
+		let color = match channels.len() {
+			3 => match (channels[0].parse(), channels[1].parse(), channels[2].parse()) {
+				(Ok(r), Ok(g), Ok(b)) => Some(Color { r, g, b }),
+				_ => None
+			},
+			_ => config::parse_hex_color(text.trim())
+		};
+
+		color.ok_or_else(|| MazeError::new(
+			MazeErrorKind::Config,
+			format!("Expected a colour as #rrggbb or r,g,b, found '{}'", text),
+		))
+	}
 }
 
 impl From<bmp::Pixel> for Color {
@@ -94,29 +120,41 @@ impl From<bmp::Pixel> for Color {
     }
 }
 
-impl Into<bmp::Pixel> for Color {
-    fn into(self) -> bmp::Pixel {
+impl From<Color> for bmp::Pixel {
+    fn from(color: Color) -> Self {
         bmp::Pixel {
-			r: self.r,
-			g: self.g,
-			b: self.b
+			r: color.r,
+			g: color.g,
+			b: color.b
 		}
     }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
-struct Coord {
-	row: u32,
-	col: u32,
+pub struct Coord {
+	pub row: u32,
+	pub col: u32,
+	// index of the image in a stack of floors, 0 for single images
+	pub floor: u32,
 }
 
 impl Coord {
-	fn new(row: u32, col: u32) -> Self {
+	pub fn new(row: u32, col: u32) -> Self {
+		Coord::on_floor(row, col, 0)
+	}
+
+	pub fn on_floor(row: u32, col: u32, floor: u32) -> Self {
 		Coord {
-			row: row,
-			col: col,
+			row,
+			col,
+			floor,
 		}
 	}
+
+	// the pixel dr rows and dc columns away on the same floor, None if it would be above or left of the image
+	pub fn offset(&self, (dr, dc): (i32, i32)) -> Option<Coord> {
+		Some(Coord::on_floor(self.row.checked_add_signed(dr)?, self.col.checked_add_signed(dc)?, self.floor))
+	}
 }
 
 impl Default for Coord {
@@ -124,13 +162,14 @@ impl Default for Coord {
         Coord {
 			row: u32::MAX,
 			col: u32::MAX,
+			floor: 0,
         }
     }
 }
 
 impl AsRef<Coord> for Coord {
     fn as_ref(&self) -> &Coord {
-        return self
+        self
     }
 }
 
@@ -138,13 +177,14 @@ impl std::ops::Add<&Coord> for &Coord {
     type Output = Coord;
 
     fn add(self, other: &Coord) -> Coord {
-        Coord {row: self.row.wrapping_add(other.row), col: self.col.wrapping_add(other.col)}
+        Coord {row: self.row.wrapping_add(other.row), col: self.col.wrapping_add(other.col), floor: self.floor.wrapping_add(other.floor)}
     }
 }
 
 
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 enum PixelType {
 	UNSET,
 	WALL,
@@ -153,36 +193,158 @@ enum PixelType {
 	ZONE,
 	START,
 	END,
+	CHECKPOINT,
+	STAIR,
+	PORTAL,
+	CONVEYOR,
+	// an area of a colour with a schedule, see schedule
+	TIMED,
+}
+
+// how the search gets from a pixel to the next one
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Move {
+	// to the neighbour (row, col) away on the same floor
+	Step((i32, i32)),
+	// up or down the stairs
+	Stair,
+	// through the portal with this number
+	Portal(usize),
+	// standing still for a tick
+	Wait,
+}
+
+// a (pixel, key combination) state waiting in the search queue
+// priority is the distance from the start plus the heuristic estimate to the end (0 for plain Dijkstra)
+struct State {
+	priority: u32,
+	dist: u32,
+	coord: Coord,
+	key_comb: usize,
+}
+
+// BinaryHeap is a max-heap, so the ordering is reversed to pop the most promising state first.
+// Equal priorities go to the larger distance, with A* that state is the closest to the end,
+// and the rest of the fields make the order total.
+impl Ord for State {
+	fn cmp(&self, other: &Self) -> Ordering {
+		let place = |state: &State| (state.coord.floor, state.coord.row, state.coord.col, state.key_comb);
+		other.priority.cmp(&self.priority)
+			.then(self.dist.cmp(&other.dist))
+			.then_with(|| place(self).cmp(&place(other)))
+	}
+}
+
+impl PartialEq for State {
+	fn eq(&self, other: &Self) -> bool {
+		self.cmp(other) == Ordering::Equal
+	}
 }
 
-#[derive(Clone)]
-struct Pixel {
+impl Eq for State {}
+
+impl PartialOrd for State {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+// the state a (pixel, key combination) state was reached from
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Link {
+	indx: u32,
+	key_comb: u32,
+	// the portal jumped through to get here, NO_PORTAL for any other move
+	portal: u32,
+}
+
+const NO_PORTAL: u32 = u32::MAX;
+const NO_LINK: Link = Link { indx: u32::MAX, key_comb: u32::MAX, portal: NO_PORTAL };
+
+// connected pixels of one coloured area (START, END, KEY or ZONE)
+#[derive(Clone, Debug)]
+struct Region {
+	// colour of the pixel the area was grown from
 	color: Color,
 	p_type: PixelType,
-	key_dists: HashMap<KeyCombRef, u32>
+	// bounding box
+	min: Coord,
+	max: Coord,
+	size: u32,
 }
 
-impl Default for Pixel {
-    fn default() -> Self {
-        Pixel {
-			color: Color {r: 0, g: 0, b: 0},
-			p_type: PixelType::UNSET,
-			key_dists: HashMap::<KeyCombRef, u32>::default(),
-        }
-    }
-}
+// region id of walls, grey pixels and not yet classified ones
+const NO_REGION: u32 = u32::MAX;
 
 
 // maze implementation
+// The grid of cells (the pixels themselves unless a topology says otherwise) of every
+// floor is stored as flat arrays indexed by pixel_indx, one floor after the other. Every key combination found
+// during the search gets a dense id and its own layer of distances in dists and of
+// predecessors in links.
 pub struct Maze {
 	key_width: u32,
 	key_height: u32,
+	// size of the cell grid of one floor
 	width: u32,
 	height: u32,
-	end: Option<(Coord, KeyCombRef)>,
+	palette: Palette,
+	// the images of the floors the maze was loaded from, the path is drawn over them through the topology
+	images: Vec<RawImage>,
+	topology: Topology,
+	// cost of taking the stairs one floor up or down
+	stair_cost: u32,
+	// format of the source image, save_path writes the same one by default
+	format: ImageFormat,
+	end: Option<(Coord, usize)>,
+	connectivity: Connectivity,
+	key_rules: KeyRules,
+	// bit of every key colour in the key combinations
 	keys: HashMap<Color, u32>,
-	key_combs: HashSet<KeyCombRef>,
-	pixels: Vec<PixelRef>,
+	// with consumable keys the bits stand for the key and door regions instead:
+	// a key area picked up or a door area opened
+	region_bits: HashMap<u32, u32>,
+	bit_regions: Vec<u32>,
+	// region ids of the START areas, in the order of Solution.start
+	start_regions: Vec<u32>,
+	// points the path has to pass before the end, see checkpoints
+	waypoints: Vec<Coord>,
+	// areas of these colours are portals, see portal
+	portals: Vec<Portal>,
+	// the portal of every portal area, by region id
+	portal_regions: HashMap<u32, usize>,
+	// (region id, exit pixel) of every area of every portal
+	portal_exits: Vec<Vec<(u32, usize)>>,
+	// when the areas of some colours can be entered
+	schedules: Vec<Schedule>,
+	// ticks after which all schedules repeat, the search keeps the tick modulo this
+	cycle: u32,
+	// what every step costs
+	cost_model: Arc<dyn CostModel>,
+	// the checkpoint number of every waypoint pixel and every pixel of a CHECKPOINT area,
+	// the waypoints come first
+	checkpoint_at: HashMap<usize, u32>,
+	// a pixel of every checkpoint
+	checkpoints: Vec<usize>,
+	// the order the checkpoints are visited in, empty while there are few enough to try all orders
+	checkpoint_order: Vec<u32>,
+	// the keys held and checkpoints visited of every search layer
+	key_combs: Vec<Progress>,
+	key_comb_ids: HashMap<Progress, usize>,
+	// the colours of the keys held in every search layer, see held_keys
+	held: Vec<Arc<[Color]>>,
+	colors: Vec<Color>,
+	p_types: Vec<PixelType>,
+	region_ids: Vec<u32>,
+	regions: Vec<Region>,
+	// pixels that are keys whatever their size, for formats that mark them explicitly
+	marked_keys: Option<Vec<bool>>,
+	dists: Vec<Vec<u32>>,
+	links: Vec<Vec<Link>>,
+	// states taken out of the queue by the last search
+	explored: u32,
+	// colours of the doors the last search couldn't pass, in the order they were met
+	blocked_doors: Vec<Color>,
 }
 
 impl Default for Maze {
@@ -192,302 +354,724 @@ impl Default for Maze {
 			key_height: 20,
 			width: 0,
 			height: 0,
+			palette: Palette::default(),
+			images: Vec::default(),
+			topology: Topology::default(),
+			stair_cost: DEFAULT_STAIR_COST,
+			format: ImageFormat::Bmp,
 			end: None,
+			connectivity: Connectivity::default(),
+			key_rules: KeyRules::default(),
 			keys: HashMap::default(),
-			key_combs: HashSet::default(),
-			pixels: Vec::default(),
+			region_bits: HashMap::default(),
+			bit_regions: Vec::default(),
+			start_regions: Vec::default(),
+			waypoints: Vec::default(),
+			portals: Vec::default(),
+			portal_regions: HashMap::default(),
+			portal_exits: Vec::default(),
+			schedules: Vec::default(),
+			cycle: 1,
+			cost_model: Arc::new(GreyCost),
+			checkpoint_at: HashMap::default(),
+			checkpoints: Vec::default(),
+			checkpoint_order: Vec::default(),
+			key_combs: Vec::default(),
+			key_comb_ids: HashMap::default(),
+			held: Vec::default(),
+			colors: Vec::default(),
+			p_types: Vec::default(),
+			region_ids: Vec::default(),
+			regions: Vec::default(),
+			marked_keys: None,
+			dists: Vec::default(),
+			links: Vec::default(),
+			explored: 0,
+			blocked_doors: Vec::default(),
 		}
     }
 }
 
 impl From<&bmp::Image> for Maze {
     fn from(item: &bmp::Image) -> Self {
-		let mut maze = Maze::default();
-		
-		maze.width = item.get_width();
-		maze.height = item.get_height();
-		
-		maze.pixels.resize_with((maze.width * maze.height) as usize, Default::default);
-		
-		for (x, y) in item.coordinates() {
-			maze.pixel_at(&Coord::new(y, x)).unwrap().borrow_mut().color = Color::from(item.get_pixel(x, y));
-		}
-		
-		maze
+		Maze::new(item, Palette::default())
     }
 }
 
+impl Maze {
+	pub fn new(item: &bmp::Image, palette: Palette) -> Self {
+		Maze::from_raw(RawImage::from(item), ImageFormat::Bmp, palette)
+	}
+
+	// Loads a BMP, PNG, PPM/PGM, GIF or text maze, the format is detected from the file contents.
+	pub fn open(file_name: &str) -> MazeResult<Maze> {
+		Maze::open_with_palette(file_name, Palette::default())
+	}
+
+	pub fn open_with_palette(file_name: &str, palette: Palette) -> MazeResult<Maze> {
+		let bytes = format::read(file_name)?;
+		Maze::decode(&bytes, palette).map_err(|e| e.context(file_name))
+	}
+
+	// same as open, but from the contents of an image file already in memory
+	pub fn decode(bytes: &[u8], palette: Palette) -> MazeResult<Maze> {
+		if ImageFormat::detect(bytes) == Some(ImageFormat::Text) {
+			return match std::str::from_utf8(bytes) {
+				Ok(text) => Maze::parse_text(text, palette),
+				Err(e) => Err(MazeError::new(MazeErrorKind::Decode, format!("Invalid text maze: {}", e)))
+			};
+		}
+
+		let (format, img) = format::decode(bytes)?;
+		Ok(Maze::from_raw(img, format, palette))
+	}
+
+	pub fn format(&self) -> ImageFormat {
+		self.format
+	}
+
+	pub fn connectivity(&self) -> Connectivity {
+		self.connectivity
+	}
+
+	// used by the next search
+	pub fn set_connectivity(&mut self, connectivity: Connectivity) {
+		self.connectivity = connectivity;
+	}
+
+	pub fn key_rules(&self) -> KeyRules {
+		self.key_rules
+	}
+
+	// used by the next search
+	pub fn set_key_rules(&mut self, key_rules: KeyRules) {
+		self.key_rules = key_rules;
+	}
+
+	pub fn topology(&self) -> Topology {
+		self.topology
+	}
+
+	// Lays the cells of the topology over the image. Coordinates, key sizes and costs are
+	// in cells from then on. Forgets the path found by the last search.
+	pub fn set_topology(&mut self, topology: Topology) -> MazeResult<()> {
+		if self.marked_keys.is_some() && !topology.is_pixels() {
+			return Err(config_error("Text mazes are made of square cells of one character."));
+		}
+		let floors: Vec<RawImage> = self.images.iter().map(|img| topology.cells(img)).collect();
+		if floors[0].colors.is_empty() {
+			return Err(config_error(format!("The {}x{} image is smaller than a cell of {}.", self.images[0].width, self.images[0].height, topology)));
+		}
+
+		self.topology = topology;
+		self.width = floors[0].width;
+		self.height = floors[0].height;
+		self.colors = floors.into_iter().flat_map(|cells| cells.colors).collect();
+		self.p_types = vec![PixelType::UNSET; self.colors.len()];
+		self.region_ids = vec![NO_REGION; self.colors.len()];
+		self.reset_search();
+		Ok(())
+	}
+
+	fn from_raw(img: RawImage, format: ImageFormat, palette: Palette) -> Self {
+		let size = img.colors.len();
+		Maze {
+			width: img.width,
+			height: img.height,
+			palette,
+			format,
+			colors: img.colors.clone(),
+			images: vec![img],
+			p_types: vec![PixelType::UNSET; size],
+			region_ids: vec![NO_REGION; size],
+			..Default::default()
+		}
+	}
+}
+
 impl Maze {
 	// private functions
-	
+
 	fn is_valid(&self, c: &Coord) -> bool {
-		c.row < self.height && c.col < self.width
+		c.row < self.height && c.col < self.width && (c.floor as usize) < self.images.len()
 	}
 
 	fn pixel_indx(&self, c: &Coord) -> MazeResult<usize> {
 		if self.is_valid(c) {
-			Ok((c.row * self.width + c.col) as usize)
+			Ok(((c.floor * self.height + c.row) * self.width + c.col) as usize)
 		} else {
 			Err(MazeError::new(
 				MazeErrorKind::CoordOutOfRange,
-				format!("Expected coords with row in [0, {:?}], col in [0, {:?}] , but passed coords: {:?}",self.height, self.width, c),
+				format!("Expected coords with row in [0, {:?}], col in [0, {:?}], floor in [0, {:?}], but passed coords: {:?}",self.height, self.width, self.images.len(), c),
 			))
 		}
 	}
-	
-	fn pixel_at(&self, c: &Coord) -> MazeResult<PixelRef> {
-		let indx = self.pixel_indx(c)?;
-		Ok(self.pixels[indx].clone())
-	}
-	
-	fn set_area_at(&self, c: &Coord) {
-		let pxl = self.pixel_at(c).unwrap();
-		
-		if pxl.borrow().color == WALL_COLOR {
-			pxl.borrow_mut().p_type = PixelType::WALL;
-		}
-		else if is_grey(&pxl.borrow().color) {
-			pxl.borrow_mut().p_type = PixelType::FREE;
-		}
-		else {
-			let mut p_type: PixelType = PixelType::ZONE;
-			if pxl.borrow().color == START_COLOR { p_type = PixelType::START; }
-			if pxl.borrow().color == END_COLOR { p_type = PixelType::END; }
-			
-			pxl.borrow_mut().p_type = p_type.clone();
-			
-			let mut key_pixels: Vec<PixelRef> = Vec::default();
-			key_pixels.reserve((self.key_height * self.key_width) as usize);
-			let (mut max_height, mut min_height, mut max_width, mut min_width) = (c.row, c.row, c.col, c.col);
-			
-			let mut wave: VecDeque<Coord> = VecDeque::new();
-			wave.push_back(c.clone());
-			key_pixels.push(pxl);
-			
-			while !wave.is_empty() {
-				let curr: Coord = wave.pop_front().unwrap();
-				let curr_pxl_color: Color = self.pixel_at(&curr).unwrap().borrow().color;
-				
-				for c in DIRS.iter() {
-					let nb: Coord = &curr + c;
-				
-					match self.pixel_at(&nb) {
-						Ok(nb_pxl) => {
-							if nb_pxl.borrow().color != curr_pxl_color
-							|| nb_pxl.borrow().p_type != PixelType::UNSET {
-								continue;
-							}
-							
-							nb_pxl.borrow_mut().p_type = p_type.clone();
-							
-							wave.push_back(nb.clone());
-							if max_height < nb.row { max_height = nb.row; }
-							if min_height > nb.row { min_height = nb.row; }
-							if max_width < nb.col { max_width = nb.col; }
-							if min_width > nb.col { min_width = nb.col; }
-							
-							if p_type == PixelType::ZONE
-								&& max_height - min_height + 1 <= self.key_height
-								&& max_width - min_width + 1 <= self.key_width
-								&& key_pixels.len() < (self.key_height * self.key_width) as usize
-							{
-								key_pixels.push(nb_pxl);
-							}
-							else if !key_pixels.is_empty() {
-								key_pixels.clear();
-							}
-						},
-						Err(MazeError{kind: MazeErrorKind::CoordOutOfRange, ..}) => {
+
+	// inverse of pixel_indx
+	fn coord_at(&self, indx: usize) -> Coord {
+		let (floor, indx) = (indx as u32 / (self.width * self.height), indx as u32 % (self.width * self.height));
+		Coord::on_floor(indx / self.width, indx % self.width, floor)
+	}
+
+	fn dist_at(&self, indx: usize, key_comb: usize) -> u32 {
+		self.dists[key_comb][indx]
+	}
+
+	// returns the dense id of the combination, adding a new distance layer for unseen ones
+	fn key_comb_id(&mut self, key_comb: Progress) -> usize {
+		if let Some(id) = self.key_comb_ids.get(&key_comb) {
+			return *id;
+		}
+
+		let id = self.key_combs.len();
+		self.held.push(self.held_keys(&key_comb.keys).into());
+		self.key_combs.push(key_comb.clone());
+		self.key_comb_ids.insert(key_comb, id);
+		self.dists.push(vec![MAX_DIST; self.colors.len()]);
+		self.links.push(vec![NO_LINK; self.colors.len()]);
+		id
+	}
+
+	fn reset_search(&mut self) {
+		self.end = None;
+		self.explored = 0;
+		self.blocked_doors.clear();
+		self.keys.clear();
+		self.region_bits.clear();
+		self.bit_regions.clear();
+		self.start_regions.clear();
+		self.checkpoint_at.clear();
+		self.checkpoints.clear();
+		self.checkpoint_order.clear();
+		self.portal_regions.clear();
+		self.portal_exits.clear();
+		self.key_combs.clear();
+		self.key_comb_ids.clear();
+		self.held.clear();
+		self.dists.clear();
+		self.links.clear();
+		self.regions.clear();
+		for p_type in self.p_types.iter_mut() {
+			*p_type = PixelType::UNSET;
+		}
+		for region_id in self.region_ids.iter_mut() {
+			*region_id = NO_REGION;
+		}
+
+		self.key_comb_id(Progress::default());
+	}
+
+	// type of a pixel judged only by its colour, like Palette::classify, but with the portals told apart from the doors
+	fn cell_type(&self, color: &Color) -> PixelType {
+		match self.palette.classify(color) {
+			PixelType::ZONE if self.portal_of(color).is_some() => PixelType::PORTAL,
+			PixelType::ZONE if self.schedule_of(color).is_some() => PixelType::TIMED,
+			p_type => p_type
+		}
+	}
+
+	// Classifies the pixel at c. Coloured pixels are flood filled into a region of matching
+	// colours; a ZONE region that is exactly a full key_height x key_width rectangle becomes a KEY.
+	// Mazes with marked_keys (text ones) say which pixels are keys instead.
+	fn set_area_at(&mut self, c: &Coord) {
+		let indx = self.pixel_indx(c).unwrap();
+		let color = self.colors[indx];
+		let p_type = self.cell_type(&color);
+
+		self.p_types[indx] = p_type;
+		if p_type == PixelType::WALL || p_type == PixelType::FREE || p_type == PixelType::CONVEYOR {
+			return;
+		}
+
+		let region_id = self.regions.len() as u32;
+		let mut region = Region {
+			color,
+			p_type,
+			min: *c,
+			max: *c,
+			size: 1,
+		};
+		let mut region_pixels: Vec<usize> = vec![indx];
+		self.region_ids[indx] = region_id;
+
+		let mut wave: VecDeque<Coord> = VecDeque::new();
+		wave.push_back(*c);
+
+		while let Some(curr) = wave.pop_front() {
+			for step in self.topology.steps(Connectivity::Four, &curr) {
+				let nb: Coord = match curr.offset(*step) {
+					Some(nb) => nb,
+					None => continue
+				};
+
+				match self.pixel_indx(&nb) {
+					Ok(nb_indx) => {
+						// в областта влизат пиксели от същия тип, а за цветните зони - и с близък цвят
+						let nb_color = self.colors[nb_indx];
+						if self.p_types[nb_indx] != PixelType::UNSET
+						|| self.cell_type(&nb_color) != p_type
+						|| (matches!(p_type, PixelType::ZONE | PixelType::PORTAL | PixelType::TIMED) && !self.palette.matches(&nb_color, &color))
+						|| self.marked_keys.as_ref().is_some_and(|marks| marks[nb_indx] != marks[indx]) {
 							continue;
-						},
-						Err(e) => panic!(e)	
-					}
+						}
+
+						self.p_types[nb_indx] = p_type;
+						self.region_ids[nb_indx] = region_id;
+						region_pixels.push(nb_indx);
+						wave.push_back(nb);
+
+						region.min = Coord::on_floor(region.min.row.min(nb.row), region.min.col.min(nb.col), c.floor);
+						region.max = Coord::on_floor(region.max.row.max(nb.row), region.max.col.max(nb.col), c.floor);
+						region.size += 1;
+					},
+					Err(MazeError{kind: MazeErrorKind::CoordOutOfRange, ..}) => {
+						continue;
+					},
+					Err(e) => panic!("{}", e)
 				}
 			}
-			
-			
-			if p_type == PixelType::ZONE
-				&& max_height - min_height + 1 == self.key_height
-				&& max_width - min_width + 1 == self.key_width
-				&& key_pixels.len() == (self.key_height * self.key_width) as usize
-			{
-				for p in key_pixels {
-					p.borrow_mut().p_type = PixelType::KEY;
-				}
+		}
+
+		let is_key = match &self.marked_keys {
+			Some(marks) => marks[indx],
+			None => region.max.row - region.min.row + 1 == self.key_height
+				&& region.max.col - region.min.col + 1 == self.key_width
+				&& region.size == self.key_height * self.key_width
+		};
+
+		if p_type == PixelType::ZONE && is_key {
+			region.p_type = PixelType::KEY;
+			for p in region_pixels {
+				self.p_types[p] = PixelType::KEY;
 			}
 		}
+
+		self.regions.push(region);
 	}
-	
-	fn get_start(&self) -> MazeResult<Coord> {
-		for  row in 0..self.height {
-			for  col in 0..self.width {
-				let curr = Coord::new(row, col);
-				if self.pixel_at(&curr).unwrap().borrow().color == START_COLOR {
-					self.set_area_at(&curr);
-					return Ok(curr)
-				}
+
+	// classifies the pixels no search has reached yet
+	fn classify_all(&mut self) {
+		for indx in 0..self.colors.len() {
+			if self.p_types[indx] == PixelType::UNSET {
+				let c = self.coord_at(indx);
+				self.set_area_at(&c);
 			}
 		}
-		
-		Err(MazeError::new(
-			MazeErrorKind::NoStart,
-			format!("There is no start."),
-		))
 	}
-	
-	fn set_end(&mut self, ends: &Vec<Coord>) -> bool {
-		if ends.len() == 0 {
-			self.end = None;
-			return false;
-		}
-		
-		let mut min_dist = MAX_DIST;
-		for end in ends {
-			let mut curr = *end;
-			let mut curr_min_dist = min_dist;
-			let mut key_comb: KeyCombRef = Rc::new(START_KEY_COMB.clone());
-			
-			let pxl = self.pixel_at(&curr).unwrap();
-			for (comb, dist) in &pxl.borrow().key_dists {
-				if dist < &curr_min_dist {
-					key_comb = comb.clone();
-					curr_min_dist = *dist;
-				}
+
+	// position of the key with this colour in the key combinations
+	fn key_pos(&self, color: &Color) -> Option<u32> {
+		match self.keys.get(color) {
+			Some(pos) => Some(*pos),
+			None => self.keys.iter()
+				.find(|(key_color, _)| self.palette.matches(key_color, color))
+				.map(|(_, pos)| *pos)
+		}
+	}
+
+	// bit of a key or door region in the key combinations, for consumable keys
+	fn region_bit(&mut self, region_id: u32) -> u32 {
+		if let Some(bit) = self.region_bits.get(&region_id) {
+			return *bit;
+		}
+
+		let bit = self.bit_regions.len() as u32;
+		self.bit_regions.push(region_id);
+		self.region_bits.insert(region_id, bit);
+		bit
+	}
+
+	// Colours of the keys held with this combination. With consumable keys a colour is
+	// listed once for every key of it picked up and not yet spent on a door.
+	fn held_keys(&self, key_comb: &KeyComb) -> Vec<Color> {
+		if self.key_rules == KeyRules::Permanent {
+			let mut key_colors: Vec<Color> = vec![WALL_COLOR; self.keys.len()];
+			for (color, pos) in &self.keys {
+				key_colors[*pos as usize] = *color;
 			}
-			
-			loop {
-				//let pxl = self.pixel_at(&curr).unwrap();
-			
-				let mut next = curr;
-				for c in DIRS.iter() {
-					let nb: Coord = &curr + c;
-			
-					match self.pixel_at(&nb) {
-						Ok(nb_pxl) => {
-							if nb_pxl.borrow().color == END_COLOR {
-								match nb_pxl.borrow().key_dists.get(&key_comb) {
-									Some(nb_dist) => {
-										if nb_dist < &curr_min_dist {
-											next = nb;
-											curr_min_dist = *nb_dist;
-										}
-									},
-									None => continue
-								}
-							}	
-						},
-						Err(MazeError{kind: MazeErrorKind::CoordOutOfRange, ..}) => {
-							continue;
-						},
-						Err(e) => panic!(e)	
-					}
-				}
-						
-						
-				if next == curr {
-					if curr_min_dist < min_dist {
-						self.end = Some((curr, key_comb));
-						min_dist = curr_min_dist;
-					}
-					
-					break;
-				}
-				
-				curr = next;
+			return key_comb.positions().map(|pos| key_colors[pos as usize]).collect();
+		}
+
+		let mut keys: Vec<Color> = Vec::new();
+		let mut spent: Vec<Color> = Vec::new();
+		for bit in key_comb.positions() {
+			let region = &self.regions[self.bit_regions[bit as usize] as usize];
+			match region.p_type {
+				PixelType::KEY => keys.push(region.color),
+				_ => spent.push(region.color)
 			}
 		}
-		
-		return true;
+		for color in spent {
+			if let Some(i) = keys.iter().position(|key| self.palette.matches(key, &color)) {
+				keys.remove(i);
+			}
+		}
+		keys
 	}
-	
-	// public functions
-	
-	pub fn find_path(&mut self, key_height: u32, key_width: u32) -> MazeResult<()> {
+
+	// Classifies every START area and returns the indices of all START pixels. The areas are
+	// numbered in start_regions by their first pixel in row-major order.
+	fn get_starts(&mut self) -> MazeResult<Vec<usize>> {
+		let mut starts: Vec<usize> = Vec::new();
+		for indx in 0..self.colors.len() {
+			if self.palette.classify(&self.colors[indx]) != PixelType::START {
+				continue;
+			}
+			if self.region_ids[indx] == NO_REGION {
+				let c = self.coord_at(indx);
+				self.set_area_at(&c);
+			}
+
+			let region_id = self.region_ids[indx];
+			if !self.start_regions.contains(&region_id) {
+				self.start_regions.push(region_id);
+			}
+			starts.push(indx);
+		}
+
+		match starts.is_empty() {
+			true => Err(MazeError::new(
+				MazeErrorKind::NoStart,
+				"There is no start.",
+			)),
+			false => Ok(starts)
+		}
+	}
+
+	// Admissible A* heuristic for every pixel: the number of steps to the nearest END pixel
+	// (Manhattan distance, or Chebyshev distance with diagonal moves) times the cheapest step
+	// in the maze, as bounded by the cost model. It ignores walls, doors and keys, so picking up a key
+	// can only make the real remaining cost bigger, never smaller than the estimate.
+	// The last step always enters an END pixel, which costs at least the cheapest of them
+	// (1 with GreyCost, times STRAIGHT_COST with diagonal moves).
+	// With several floors the estimate is the same on all of them: the END pixels of every floor
+	// are laid over one another and the stairs are taken for free.
+	// Returns None if there is no END pixel at all.
+	fn end_heuristic(&self) -> Option<Vec<u32>> {
+		let (width, height) = (self.width as usize, self.height as usize);
+		let mut min_weight = MAX_DIST;
+		let mut end_weight = MAX_DIST;
+		let mut steps: Vec<u32> = vec![MAX_DIST; width * height];
+
+		for (indx, color) in self.colors.iter().enumerate() {
+			match self.palette.classify(color) {
+				PixelType::END => {
+					steps[indx % (width * height)] = 0;
+					end_weight = end_weight.min(self.cost_model.min_cost(&self.cost_cell(&self.coord_at(indx), indx, None)));
+				},
+				PixelType::WALL => {},
+				_ => min_weight = min_weight.min(self.cost_model.min_cost(&self.cost_cell(&self.coord_at(indx), indx, None)))
+			}
+		}
+
+		if !steps.contains(&0) {
+			return None;
+		}
+
+		// two pass distance transform - top-left to bottom-right and back
+		let diagonal = self.topology.has_diagonal_steps(self.connectivity);
+		for row in 0..height {
+			for col in 0..width {
+				let indx = row * width + col;
+				if row > 0 { steps[indx] = steps[indx].min(steps[indx - width].saturating_add(1)); }
+				if col > 0 { steps[indx] = steps[indx].min(steps[indx - 1].saturating_add(1)); }
+				if diagonal && row > 0 && col > 0 { steps[indx] = steps[indx].min(steps[indx - width - 1].saturating_add(1)); }
+				if diagonal && row > 0 && col + 1 < width { steps[indx] = steps[indx].min(steps[indx - width + 1].saturating_add(1)); }
+			}
+		}
+		for row in (0..height).rev() {
+			for col in (0..width).rev() {
+				let indx = row * width + col;
+				if row + 1 < height { steps[indx] = steps[indx].min(steps[indx + width].saturating_add(1)); }
+				if col + 1 < width { steps[indx] = steps[indx].min(steps[indx + 1].saturating_add(1)); }
+				if diagonal && row + 1 < height && col + 1 < width { steps[indx] = steps[indx].min(steps[indx + width + 1].saturating_add(1)); }
+				if diagonal && row + 1 < height && col > 0 { steps[indx] = steps[indx].min(steps[indx + width - 1].saturating_add(1)); }
+			}
+		}
+
+		if min_weight == MAX_DIST {
+			min_weight = 1;
+		}
+		let scale = match self.topology.connectivity(self.connectivity) {
+			Connectivity::Four => 1,
+			_ => STRAIGHT_COST
+		};
+
+		Some(steps.into_iter().map(|dist| match dist {
+			0 => 0,
+			_ => (dist - 1).saturating_mul(min_weight).saturating_add(end_weight).saturating_mul(scale),
+		}).collect())
+	}
+
+	fn search(&mut self, key_size: KeySize, astar: bool) -> MazeResult<()> {
+		self.search_from(key_size, astar, None)
+	}
+
+	// Dijkstra over (pixel, key combination) states, or A* guided by end_heuristic.
+	// Every START pixel is a source at distance 0, or only the pixels of the start
+	// area with this number. Stops at the first END pixel taken out of the queue.
+	fn search_from(&mut self, key_size: KeySize, astar: bool, start_area: Option<usize>) -> MazeResult<()> {
+		let (key_height, key_width) = match key_size {
+			KeySize::Fixed { height, width } => (height, width),
+			KeySize::Auto => self.detect_key_size()?.unwrap_or((0, 0)),
+			KeySize::Any => (0, 0)
+		};
 		self.key_height = key_height;
 		self.key_width = key_width;
-	
-		let start = self.get_start()?;
-		self.key_combs.insert(Rc::new(START_KEY_COMB.clone()));
-		self.pixel_at(&start).unwrap().borrow_mut().key_dists.insert(
-			self.key_combs.iter().next().unwrap().clone(),
-			0
-		);
-		
-		let mut wave: VecDeque<(Coord, KeyCombRef)> = VecDeque::new();
-		wave.push_back( (
-			start,
-			self.key_combs.iter().next().unwrap().clone()
-		));
-		
-		let mut ends: Vec<Coord> = Vec::new();
-		
-		while !wave.is_empty() {
-			let curr: (Coord, KeyCombRef) = wave.pop_front().unwrap();
-			let curr_dist = match self.pixel_at(&curr.0).unwrap().borrow_mut().key_dists.get(&curr.1) {
-				Some(dist) => *dist,
+		self.reset_search();
+		if key_size == KeySize::Any {
+			self.promote_key_like_regions();
+		}
+
+		let mut starts = self.get_starts()?;
+		if let Some(area) = start_area {
+			let region_id = self.start_regions.get(area).copied();
+			starts.retain(|indx| Some(self.region_ids[*indx]) == region_id);
+		}
+		self.find_checkpoints(&starts)?;
+		self.find_portals();
+		self.cycle = self.schedule_cycle()?;
+
+		let heuristic: Option<Vec<u32>> = match astar {
+			true => match self.end_heuristic() {
+				// през портал оценката може да е по-голяма от истинската цена
+				Some(h) => match self.portal_bound(|c| h[(c.row * self.width + c.col) as usize]) {
+					Some(bound) => Some(h.into_iter().map(|estimate| estimate.min(bound)).collect()),
+					None => Some(h)
+				},
 				None => return Err(MazeError::new(
-						MazeErrorKind::Other,
-						format!("Current pixel doesn't have current combination."),
-					))
-			};
+					MazeErrorKind::NoEnd,
+					"There is no end zone.",
+				))
+			},
+			false => None
+		};
+		let width = self.width;
+		// оценката е една и съща за всички етажи
+		let estimate = move |c: &Coord| -> u32 {
+			match &heuristic {
+				Some(h) => h[(c.row * width + c.col) as usize],
+				None => 0
+			}
+		};
+
+		let connectivity = self.topology.connectivity(self.connectivity);
+		// пътищата, чиято цена не се побира в u32, се пропускат
+		let mut overflow = false;
+		let mut heap: BinaryHeap<State> = BinaryHeap::new();
+		for start_indx in starts {
+			let start = self.coord_at(start_indx);
+			let key_comb = self.key_comb_id(self.start_progress(start_indx));
+			self.dists[key_comb][start_indx] = 0;
+			heap.push(State {
+				priority: estimate(&start),
+				dist: 0,
+				coord: start,
+				key_comb,
+			});
+		}
+
+		while let Some(State { dist: curr_dist, coord: curr, key_comb: curr_comb, .. }) = heap.pop() {
+			let curr_indx = self.pixel_indx(&curr)?;
+
+			// пропускаме остарелите записи - състоянието вече е обработено с по-малка дистанция
+			if self.dist_at(curr_indx, curr_comb) < curr_dist {
+				continue;
+			}
+			self.explored += 1;
+
+			// първият изваден END пиксел е най-близкият до началото (след като е минал през всички контролни точки)
+			if self.p_types[curr_indx] == PixelType::END && self.key_combs[curr_comb].visited.len() as usize == self.checkpoints.len() {
+				self.end = Some((curr, curr_comb));
+				return Ok(());
+			}
 
-			for c in DIRS.iter() {
+			// съседите в етажа, от стълбите - и същата клетка на етажите над и под него,
+			// от портал - изходите на другите му области, а с разписания - и чакане на място
+			let topology = self.topology;
+			let stairs = self.stairs_from(&curr);
+			let wait = (self.cycle > 1).then_some((Some(curr), Move::Wait));
+			let moves = topology.steps(connectivity, &curr).iter()
+				.map(|step| (curr.offset(*step), Move::Step(*step)))
+				.chain(stairs.iter().map(|nb| (*nb, Move::Stair)))
+				.chain(self.portal_jumps(curr_indx))
+				.chain(wait);
+			let next_tick = (self.key_combs[curr_comb].tick + 1) % self.cycle;
+			let uses_heading = self.cost_model.uses_heading();
+			let held_keys = self.held[curr_comb].clone();
+			let curr_cell = self.cost_cell(&curr, curr_indx, self.key_combs[curr_comb].heading);
+
+			for (nb, step) in moves {
 				// взимаме съседа на текущия пиксел
-				let nb: Coord = &curr.0 + c;
-				
-				match self.pixel_at(&nb) {
-					Ok(nb_pxl) => {
-						if nb_pxl.borrow().p_type == PixelType::UNSET {
+				let nb: Coord = match nb {
+					Some(nb) => nb,
+					None => continue
+				};
+
+				match self.pixel_indx(&nb) {
+					Ok(nb_indx) => {
+						if self.p_types[nb_indx] == PixelType::UNSET {
 							self.set_area_at(&nb);
-							if nb_pxl.borrow().p_type == PixelType::END {
-								ends.push(nb.clone());
-							}
-						}	
-						
+						}
+
+						let nb_type = self.p_types[nb_indx];
+						let nb_color = self.colors[nb_indx];
+						// цветът на областта, от която е пикселът
+						let nb_region_color = match self.region_ids[nb_indx] {
+							NO_REGION => nb_color,
+							id => self.regions[id as usize].color
+						};
+
 						// ако е стена я пропускаме
-						if nb_pxl.borrow().p_type  == PixelType::WALL { continue; }
-						
+						if nb_type == PixelType::WALL { continue; }
+
+						// стълбите водят само до стълби на съседния етаж
+						if step == Move::Stair && nb_type != PixelType::STAIR { continue; }
+
+						// по време на следващия ход областта трябва да е отворена
+						if nb_type == PixelType::TIMED && !self.schedule_of(&nb_region_color).is_some_and(|schedule| schedule.is_open(next_tick)) {
+							continue;
+						}
+
+						// по конвейер се минава само в посоката му
+						if matches!(step, Move::Step(step) if !self.conveyor_allows(curr_indx, nb_indx, step)) { continue; }
+
+						// заключен портал се ползва само с ключ от неговия цвят
+						if let Move::Portal(portal) = step {
+							if let Some(key) = self.portals[portal].key {
+								if !held_keys.iter().any(|held| self.palette.matches(held, &key)) {
+									if !self.blocked_doors.contains(&key) {
+										self.blocked_doors.push(key);
+									}
+									continue;
+								}
+							}
+						}
+
+						// по диагонал не се минава, ако някоя от двете съседни клетки отстрани на хода е стена
+						if connectivity == Connectivity::Eight && matches!(step, Move::Step(step) if topology.is_diagonal(step)) {
+							let is_wall = |c: Coord| self.pixel_indx(&c).is_ok_and(|indx| self.palette.classify(&self.colors[indx]) == PixelType::WALL);
+							if is_wall(Coord { row: nb.row, ..curr }) || is_wall(Coord { col: nb.col, ..curr }) {
+								continue;
+							}
+						}
+
 						// изчисляваме цената за преминаване в съседа
-						let weight: u32 = match is_grey(&nb_pxl.borrow().color) {
-							true => nb_pxl.borrow().color.r as u32,
-							false => 1
+						let weight: u32 = match step {
+							Move::Step(step) => match self.cost_model.cost(&curr_cell, &self.cost_cell(&nb, nb_indx, Some(step)), &held_keys) {
+								Some(cost) => connectivity.step_cost(cost, topology.is_diagonal(step)),
+								None => continue
+							},
+							Move::Stair => connectivity.step_cost(self.stair_cost, false),
+							Move::Portal(portal) => connectivity.step_cost(self.portals[portal].cost, false),
+							Move::Wait => connectivity.step_cost(WAIT_COST, false)
 						};
-						
+
 						// ако новият пиксел е цветен:
 						//  - ако е ключ - добавяме го (ако вече не е добавен)
 						//	- ако не е ключ - проверяваме дали има ключ с такъв цвят и дали текущата комбинация съдържа този цвят
 						//	  ако не го съдържа - отиваме към следващия съсед
 						//	  ако го съдържа - минаваме през него и изчисляваме новата цена
 						// ако не е цветен -  минаваме през него и изчисляваме новата цена
-						let mut new_key_comb = curr.1.clone();
-						
-						if nb_pxl.borrow().p_type == PixelType::KEY {
-							let len = self.keys.len();
-							let pos: &u32 = self.keys.entry(nb_pxl.borrow().color).or_insert(len as u32);
-
-							new_key_comb = Rc::new(keycomb_set(&curr.1, *pos));
-							self.key_combs.insert(new_key_comb.clone());
+						let mut new_key_comb = curr_comb;
+
+						if self.key_rules == KeyRules::Consumable && (nb_type == PixelType::KEY || nb_type == PixelType::ZONE) {
+							// всяка област-ключ се взима веднъж, а всяка врата се отваря веднъж и остава отворена
+							let region_id = self.region_ids[nb_indx];
+							let bit = self.region_bit(region_id);
+							let key_comb = &self.key_combs[curr_comb];
+							if !key_comb.keys.has(bit) {
+								let opened = nb_type == PixelType::KEY || self.held_keys(&key_comb.keys).iter()
+									.any(|key| self.palette.matches(key, &nb_region_color));
+								if !opened {
+									if !self.blocked_doors.contains(&nb_region_color) {
+										self.blocked_doors.push(nb_region_color);
+									}
+									continue;
+								}
+
+								let key_comb = key_comb.with_key(bit);
+								new_key_comb = self.key_comb_id(key_comb);
+							}
 						}
-						else if nb_pxl.borrow().p_type == PixelType::ZONE {
-							
-							match self.keys.get(&nb_pxl.borrow().color) {
-								Some(pos) => {
-									if !keycomb_eq(&keycomb_set(&new_key_comb, *pos), &new_key_comb) { continue; }
-								},
-								None => continue
+						else if nb_type == PixelType::KEY {
+							let pos: u32 = match self.key_pos(&nb_region_color) {
+								Some(pos) => pos,
+								None => {
+									let pos = self.keys.len() as u32;
+									self.keys.insert(nb_region_color, pos);
+									pos
+								}
+							};
+
+							let key_comb = self.key_combs[curr_comb].with_key(pos);
+							new_key_comb = self.key_comb_id(key_comb);
+						}
+						else if nb_type == PixelType::ZONE {
+
+							let opened = match self.key_pos(&nb_region_color) {
+								Some(pos) => self.key_combs[curr_comb].keys.has(pos),
+								None => false
+							};
+							if !opened {
+								// запомняме вратата за диагностиката, ако краят се окаже недостижим
+								if !self.blocked_doors.contains(&nb_region_color) {
+									self.blocked_doors.push(nb_region_color);
+								}
+								continue;
+							}
+						}
+
+						if let Some(checkpoint) = self.checkpoint_at.get(&nb_indx) {
+							let progress = &self.key_combs[new_key_comb];
+							let next = match self.checkpoint_order.is_empty() {
+								true => !progress.visited.has(*checkpoint),
+								false => self.checkpoint_order.get(progress.visited.len() as usize) == Some(checkpoint)
+							};
+							if next {
+								let progress = progress.with_visited(*checkpoint);
+								new_key_comb = self.key_comb_id(progress);
 							}
 						}
-						
+
+						if self.cycle > 1 {
+							let progress = self.key_combs[new_key_comb].with_tick(next_tick);
+							new_key_comb = self.key_comb_id(progress);
+							self.check_tick_layers(self.dists.len())?;
+						}
+
+						// завоят се плаща спрямо посоката, с която е стъпено в пиксела
+						if uses_heading {
+							let heading = match step {
+								Move::Step(step) => Some(step),
+								_ => None
+							};
+							let progress = self.key_combs[new_key_comb].with_heading(heading);
+							new_key_comb = self.key_comb_id(progress);
+						}
+
 						// ако съседния пиксел няма разстояние със новата комбинация или старото такова е по голямо от новото
-						// тогава актуализираме разстоянието
-						if nb_pxl.borrow().key_dists.get(&new_key_comb) == None
-							|| nb_pxl.borrow().key_dists[&new_key_comb] > (curr_dist + weight)
-						{
-							nb_pxl.borrow_mut().key_dists.insert(new_key_comb.clone(), curr_dist + weight);
-							wave.push_back( (
-								nb,
-								new_key_comb
-							));
+						// тогава актуализираме разстоянието и го добавяме в опашката
+						let new_dist = match curr_dist.checked_add(weight) {
+							Some(new_dist) if new_dist < MAX_DIST => new_dist,
+							_ => {
+								overflow = true;
+								continue;
+							}
+						};
+						if self.dist_at(nb_indx, new_key_comb) > new_dist {
+							self.dists[new_key_comb][nb_indx] = new_dist;
+							let portal = match step {
+								Move::Portal(portal) => portal as u32,
+								_ => NO_PORTAL
+							};
+							self.links[new_key_comb][nb_indx] = Link { indx: curr_indx as u32, key_comb: curr_comb as u32, portal };
+							heap.push(State {
+								priority: new_dist.saturating_add(estimate(&nb)),
+								dist: new_dist,
+								coord: nb,
+								key_comb: new_key_comb,
+							});
 						}
 					},
 					Err(MazeError{kind: MazeErrorKind::CoordOutOfRange, ..}) => {
@@ -497,109 +1081,263 @@ impl Maze {
 				}
 			}
 		}
-		
-		match self.set_end(&ends) {
-			true => return Ok(()),
-			false => return Err(MazeError::new(
-				MazeErrorKind::NoEnd,
-				format!("There is no end zone."),
-			))
+
+		match self.checkpoint_error() {
+			Some(e) => Err(e),
+			None if overflow => Err(config_error(format!("The cost of the path to the end exceeds {}, lower the step costs.", MAX_DIST - 1))),
+			None => Err(self.unreachable_error())
 		}
 	}
-	
-	pub fn save_path(&self, file_name: &str) -> MazeResult<()> {
-		let (mut curr, mut key_comb) = match &self.end {
-			Some((coord, comb)) => (*coord, comb.clone()),
+
+	// public functions
+
+	pub fn find_path(&mut self, key_height: u32, key_width: u32) -> MazeResult<()> {
+		self.search(KeySize::Fixed { height: key_height, width: key_width }, false)
+	}
+
+	// Same result as find_path, but guided towards the nearest END pixel, so on mazes
+	// where the end is far from the start it explores much less of the image.
+	pub fn find_path_astar(&mut self, key_height: u32, key_width: u32) -> MazeResult<()> {
+		self.search(KeySize::Fixed { height: key_height, width: key_width }, true)
+	}
+
+	// find_path or find_path_astar for keys of a detected or of any size
+	pub fn find_path_with(&mut self, key_size: KeySize, astar: bool) -> MazeResult<()> {
+		self.search(key_size, astar)
+	}
+
+	// The best path from every START area on its own, in the order of Solution.start.
+	// Areas with no way to the end get the Unreachable error of their search, any other
+	// error stops the whole run. Leaves the maze with the search of the last area.
+	pub fn solve_each_start(&mut self, key_size: KeySize, astar: bool) -> MazeResult<Vec<MazeResult<Solution>>> {
+		self.reset_search();
+		self.get_starts()?;
+
+		let mut solutions: Vec<MazeResult<Solution>> = Vec::new();
+		for area in 0..self.start_regions.len() {
+			match self.search_from(key_size, astar, Some(area)) {
+				Ok(()) => solutions.push(self.solution()),
+				Err(e) if e.kind == MazeErrorKind::Unreachable => solutions.push(Err(e)),
+				Err(e) => return Err(e)
+			}
+		}
+		Ok(solutions)
+	}
+
+	// The path of a maze that has to have exactly one START area, for callers that rely on it.
+	// More areas give MultipleStarts at the first pixel of the second one instead of a search.
+	pub fn solve_single_start(&mut self, key_size: KeySize, astar: bool) -> MazeResult<Solution> {
+		self.reset_search();
+		let starts = self.get_starts()?;
+		if let Some(second) = self.start_regions.get(1).copied() {
+			let indx = starts.into_iter().find(|indx| self.region_ids[*indx] == second).unwrap_or_default();
+			return Err(MazeError::new(
+				MazeErrorKind::MultipleStarts,
+				"There is more than one start area.",
+			).at(self.coord_at(indx)));
+		}
+
+		self.search(key_size, astar)?;
+		self.solution()
+	}
+
+	// Follows the links back from the end found by the last search and returns the route from the start.
+	pub fn solution(&self) -> MazeResult<Solution> {
+		let (end, end_comb) = match &self.end {
+			Some((coord, comb)) => (*coord, *comb),
 			None => return Err(MazeError::new(
 				MazeErrorKind::NoEnd,
-				format!("There is no end zone."),
+				"There is no end zone.",
 			))
 		};
-		
-		let mut img = bmp::Image::new(self.width, self.height);
 
-		for (x, y) in img.coordinates() {
-			img.set_pixel(x, y, self.pixel_at(&Coord::new(y, x)).unwrap().borrow().color.into());
-		}
-		
+		// състоянията от края към началото, с портала, през който е скочено до всяко
+		let mut states: Vec<(usize, usize, u32)> = Vec::new();
+		let mut curr = (self.pixel_indx(&end)?, end_comb);
 		loop {
-			let pxl = self.pixel_at(&curr).unwrap();
-			
-			img.set_pixel(curr.col, curr.row, PATH_COLOR.into());
-			
-			if pxl.borrow().p_type == PixelType::START && keycomb_eq(&key_comb, &START_KEY_COMB.clone()) { 
+			let link = self.links[curr.1][curr.0];
+			states.push((curr.0, curr.1, link.portal));
+			if link == NO_LINK {
 				break;
 			}
-		
-			// намираме съседа с минимална дистанция от тази комбинация
-            // ако сме в ключ с комбинация, която той няма, значи сме излезли от него и сме с 1 комбинация назад
-            // тогава цената на следващия пиксел с новата комбинация не зависи от тази на ключа(приемаме я за MAX_DIST)
-			let mut next = curr;
-			let mut min_dist = MAX_DIST;
-			if pxl.borrow().p_type == PixelType::KEY && pxl.borrow().key_dists.get(&key_comb) != None {
-                min_dist = pxl.borrow().key_dists[&key_comb];
-            }
-			
-			for c in DIRS.iter() {
-				let nb: Coord = &curr + c;
-		
-				// взимаме съседния пиксел на текущия пиксел
-				match self.pixel_at(&nb) {
-					Ok(nb_pxl) => {
-						// ако съседния пиксел има цена с текущата комбинация го обработваме
-						match nb_pxl.borrow().key_dists.get(&key_comb) {
-							Some(nb_dist) => {
-								if nb_dist < &min_dist {
-									next = nb;
-									min_dist = *nb_dist;
-								}
-							},
-							None => continue
-						}
-					},
-					Err(MazeError{kind: MazeErrorKind::CoordOutOfRange, ..}) => {
-						continue;
-					},
-					Err(e) => panic!(e)	
+			curr = (link.indx as usize, link.key_comb as usize);
+		}
+
+		if self.p_types[curr.0] != PixelType::START || self.key_comb_ids.get(&self.start_progress(curr.0)) != Some(&curr.1) {
+			return Err(MazeError::new(
+				MazeErrorKind::InternalInvariant,
+				"There is no path, but self.end is not None.",
+			).at(self.coord_at(curr.0)));
+		}
+
+		let mut solution = Solution {
+			cost: self.dist_at(states[0].0, end_comb),
+			start: self.start_regions.iter().position(|id| *id == self.region_ids[curr.0]).unwrap_or(0),
+			..Default::default()
+		};
+		let mut prev_comb = START_KEY_COMB_ID;
+		for (indx, key_comb, portal) in states.into_iter().rev() {
+			if portal != NO_PORTAL {
+				solution.jumps.push((self.portals[portal as usize].color, solution.steps.len()));
+			}
+
+			let keys: Vec<Color> = self.held_keys(&self.key_combs[key_comb].keys);
+
+			// ключ е взет, ако вече се държат повече ключове от цвета му
+			if let Some(prev) = solution.steps.last() {
+				for (i, color) in keys.iter().enumerate() {
+					let held = keys[..=i].iter().filter(|key| *key == color).count();
+					if prev.keys.iter().filter(|key| *key == color).count() < held {
+						solution.pickups.push((*color, solution.steps.len()));
+					}
 				}
 			}
-					
-			// Ако няма съсед с по-малка дистанция:
-            //  - ако сме в ключ тогава махаме цвета на ключа от комбинацията и проверяваме тогава съседите
-            //  - ако сме в поле различно от ключ значи пряк няма път	
-			if next == curr {
-				if pxl.borrow().p_type == PixelType::KEY {
-					match self.keys.get(&pxl.borrow().color) {
-						Some(pos) => {
-							let prev_comb = Rc::new(keycomb_unset(&key_comb, *pos));
-							key_comb = self.key_combs.get(&prev_comb).unwrap().clone();
-						},
-						None => return Err(MazeError::new(
-							MazeErrorKind::Other,
-							format!("Key color not included in slef.keys"),
-						))
+
+			let (prev, progress) = (&self.key_combs[prev_comb], &self.key_combs[key_comb]);
+			if self.key_rules == KeyRules::Consumable && key_comb != prev_comb {
+				for bit in progress.keys.positions().filter(|bit| !prev.keys.has(*bit)) {
+					let region = &self.regions[self.bit_regions[bit as usize] as usize];
+					if region.p_type == PixelType::ZONE {
+						solution.openings.push((region.color, solution.steps.len()));
 					}
 				}
-				else {
-					return Err(MazeError::new(
-						MazeErrorKind::NoEnd,
-						format!("There is no path, but self.end is not None."),
-					));
+			}
+			for checkpoint in progress.visited.positions().filter(|checkpoint| !prev.visited.has(*checkpoint)) {
+				solution.checkpoints.push((checkpoint as usize, solution.steps.len()));
+			}
+			prev_comb = key_comb;
+
+			solution.steps.push(Step {
+				coord: self.coord_at(indx),
+				dist: self.dist_at(indx, key_comb),
+				time: solution.steps.len() as u32,
+				keys,
+			});
+		}
+
+		Ok(solution)
+	}
+
+	// number of (pixel, key combination) states the last search expanded
+	pub fn states_explored(&self) -> u32 {
+		self.explored
+	}
+
+	pub fn solve(&mut self, key_height: u32, key_width: u32) -> MazeResult<Solution> {
+		self.find_path(key_height, key_width)?;
+		self.solution()
+	}
+
+	// Draws the path over the maze. The format is taken from the extension of file_name
+	// and falls back to the format the maze was loaded from. With consumable keys the doors
+	// get numbered in the order they were opened (images only, if the number fits in the door).
+	// A maze of several floors is written to one file per floor, see floor_file_name.
+	pub fn save_path(&self, file_name: &str) -> MazeResult<()> {
+		let solution = self.solution()?;
+		self.write_image(file_name, &[solution])
+	}
+
+	// like save_path, for several paths at once, e.g. the ones of solve_each_start
+	pub fn save_paths(&self, solutions: &[Solution], file_name: &str) -> MazeResult<()> {
+		self.write_image(file_name, solutions)
+	}
+
+	// writes the maze as it is, without a path, the format is chosen like in save_path
+	pub fn save(&self, file_name: &str) -> MazeResult<()> {
+		self.write_image(file_name, &[])
+	}
+
+	// the image pixels of the cells of a region, over the whole image of its floor
+	fn region_mask(&self, region_id: u32, img: &RawImage) -> Vec<bool> {
+		let mut mask = vec![false; img.colors.len()];
+		for indx in (0..self.region_ids.len()).filter(|indx| self.region_ids[*indx] == region_id) {
+			for (x, y) in self.topology.pixels(&self.coord_at(indx), img.width, img.height) {
+				mask[(y * img.width + x) as usize] = true;
+			}
+		}
+		mask
+	}
+
+	fn write_image(&self, file_name: &str, solutions: &[Solution]) -> MazeResult<()> {
+		let format = ImageFormat::from_extension(file_name).unwrap_or(self.format);
+		for floor in 0..self.images.len() as u32 {
+			let floor_file = self.floor_file_name(file_name, floor);
+			if format == ImageFormat::Text {
+				format::write_bytes(self.draw_text(solutions, floor)?.as_bytes(), &floor_file)?;
+				continue;
+			}
+
+			let mut img = self.images[floor as usize].clone();
+			for solution in solutions {
+				// пикселите, на които се чака, са с отделен цвят
+				let waits: Vec<usize> = solution.waits().collect();
+				for (i, coord) in solution.coords().enumerate() {
+					self.pixel_indx(coord)?;
+					if coord.floor != floor {
+						continue;
+					}
+					let color = match waits.contains(&i) {
+						true => self.palette.wait,
+						false => self.palette.path
+					};
+					for (x, y) in self.topology.pixels(coord, img.width, img.height) {
+						img.colors[(y * img.width + x) as usize] = color;
+					}
+				}
+
+				// номерата на отворените врати в реда на отваряне, с цвета на стените
+				for (order, (_, step)) in solution.openings.iter().enumerate() {
+					let coord = &solution.steps[*step].coord;
+					let (region_id, region) = match self.region_ids[self.pixel_indx(coord)?] {
+						_ if coord.floor != floor => continue,
+						NO_REGION => continue,
+						id => (id, &self.regions[id as usize])
+					};
+					let mask = self.region_mask(region_id, &img);
+					label::draw_number(&mut img, order + 1, self.topology.pixel_box(&region.min, &region.max), &mask, self.palette.wall);
+				}
+
+				// двата края на всеки скок през портал с един и същ номер
+				for (order, (_, step)) in solution.jumps.iter().enumerate() {
+					for coord in [&solution.steps[*step - 1].coord, &solution.steps[*step].coord] {
+						let (region_id, region) = match self.region_ids[self.pixel_indx(coord)?] {
+							_ if coord.floor != floor => continue,
+							NO_REGION => continue,
+							id => (id, &self.regions[id as usize])
+						};
+						let mask = self.region_mask(region_id, &img);
+						label::draw_number(&mut img, order + 1, self.topology.pixel_box(&region.min, &region.max), &mask, self.palette.wall);
+					}
 				}
 			}
-			else {
-                curr = next;
-            }
+
+			format::write_bytes(&format::encode(&img, format)?, &floor_file)?;
 		}
-		
-		let _ = img.save(file_name);
-		
 		Ok(())
 	}
 }
 
-
 mod error;
+mod solution;
+mod palette;
+mod config;
+mod format;
+mod text;
+mod info;
+mod batch;
+mod diagnosis;
+mod lint;
+mod key_size;
+mod key_comb;
+mod rules;
+mod checkpoint;
+mod label;
+mod topology;
+mod floors;
+mod portal;
+mod conveyor;
+mod schedule;
+mod cost;
 
 #[cfg(test)]
-mod tests;
\ No newline at end of file
+mod tests;
@@ -0,0 +1,153 @@
+// Checks for the usual drawing mistakes, so they are found before a search
+// silently treats a broken key as a door.
+
+use std::fmt;
+
+use super::{grey_level, Color, Coord, Maze, PixelType, Region};
+
+// (top left, bottom right)
+type BBox = (Coord, Coord);
+
+// how far (in every channel) a colour may be from the start/end colour to be reported as close to it
+const NEAR_RESERVED: u8 = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LintKind {
+	// a solid area almost as big as a key, the size is width x height of its bounding box
+	NearMissKey { width: u32, height: u32 },
+	// another key with the colour of an earlier one
+	DuplicateKey,
+	// a door with no key of its colour
+	DoorWithoutKey,
+	// a key with no door of its colour
+	KeyWithoutDoor,
+	// a START area after the first one
+	MultipleStarts,
+	// grey floor with weight 0, walking over it is free; count is the number of such pixels
+	ZeroWeight { count: u32 },
+	// a colour that isn't grey and is almost, but not within the tolerance of, the start or end colour
+	NearReservedColor { reserved: Color },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Lint {
+	pub kind: LintKind,
+	pub color: Color,
+	// bounding box of the area the lint is about
+	pub bbox: BBox,
+}
+
+impl fmt::Display for Lint {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		let (min, max) = self.bbox;
+		match self.kind {
+			LintKind::NearMissKey { width, height } => write!(fmt, "The {} area is {}x{}, almost the size of a key", self.color, width, height)?,
+			LintKind::DuplicateKey => write!(fmt, "There is more than one {} key", self.color)?,
+			LintKind::DoorWithoutKey => write!(fmt, "There is no key for the {} door", self.color)?,
+			LintKind::KeyWithoutDoor => write!(fmt, "There is no door for the {} key", self.color)?,
+			LintKind::MultipleStarts => write!(fmt, "There is more than one start area")?,
+			LintKind::ZeroWeight { count } => write!(fmt, "{} grey pixels have weight 0", count)?,
+			LintKind::NearReservedColor { reserved } => write!(fmt, "The colour {} is close to the reserved {}", self.color, reserved)?,
+		}
+		write!(fmt, " at ({}, {}) - ({}, {})", min.row, min.col, max.row, max.col)
+	}
+}
+
+fn expand((min, max): BBox, c: Coord) -> BBox {
+	(
+		Coord::on_floor(min.row.min(c.row), min.col.min(c.col), min.floor.min(c.floor)),
+		Coord::on_floor(max.row.max(c.row), max.col.max(c.col), max.floor.max(c.floor)),
+	)
+}
+
+impl Maze {
+	// Classifies the whole maze with this key size and lists what looks wrong with it,
+	// in the order of the checks. Forgets the path found by the last search.
+	pub fn validate(&mut self, key_height: u32, key_width: u32) -> Vec<Lint> {
+		self.key_height = key_height;
+		self.key_width = key_width;
+		self.reset_search();
+		self.classify_all();
+
+		let mut lints: Vec<Lint> = Vec::new();
+		let lint = |kind: LintKind, region: &Region| Lint { kind, color: region.color, bbox: (region.min, region.max) };
+		let same_color = |a: &Region, b: &Region| self.palette.matches(&a.color, &b.color);
+		let is_key = |region: &&Region| region.p_type == PixelType::KEY;
+		let is_door = |region: &&Region| region.p_type == PixelType::ZONE;
+
+		// до 10% разлика в размерите (поне един пиксел) и поне 90% запълнен правоъгълник
+		let near = |size: u32, key: u32| size.abs_diff(key) <= (key / 10).max(1);
+		let mut near_misses: Vec<usize> = Vec::new();
+		if self.marked_keys.is_none() {
+			for (id, region) in self.regions.iter().enumerate().filter(|(_, region)| is_door(region)) {
+				let (height, width) = (region.max.row - region.min.row + 1, region.max.col - region.min.col + 1);
+				let has_key = self.regions.iter().filter(is_key).any(|key| same_color(key, region));
+				if !has_key && near(height, key_height) && near(width, key_width) && region.size * 10 >= height * width * 9 {
+					lints.push(lint(LintKind::NearMissKey { width, height }, region));
+					near_misses.push(id);
+				}
+			}
+		}
+
+		let keys: Vec<&Region> = self.regions.iter().filter(is_key).collect();
+		for (i, key) in keys.iter().enumerate() {
+			if keys[..i].iter().any(|other| same_color(other, key)) {
+				lints.push(lint(LintKind::DuplicateKey, key));
+			}
+		}
+
+		// вратите с един и същи цвят се докладват веднъж
+		let doors: Vec<(usize, &Region)> = self.regions.iter().enumerate().filter(|(_, region)| is_door(region)).collect();
+		for (i, (id, door)) in doors.iter().enumerate() {
+			let reported = doors[..i].iter().any(|(_, other)| same_color(other, door));
+			if !reported && !near_misses.contains(id) && !keys.iter().any(|key| same_color(key, door)) {
+				lints.push(lint(LintKind::DoorWithoutKey, door));
+			}
+		}
+
+		for (i, key) in keys.iter().enumerate() {
+			let reported = keys[..i].iter().any(|other| same_color(other, key));
+			if !reported && !doors.iter().any(|(_, door)| same_color(door, key)) {
+				lints.push(lint(LintKind::KeyWithoutDoor, key));
+			}
+		}
+
+		for start in self.regions.iter().filter(|region| region.p_type == PixelType::START).skip(1) {
+			lints.push(lint(LintKind::MultipleStarts, start));
+		}
+
+		let mut zero_weight: (u32, Option<BBox>) = (0, None);
+		let mut near_reserved: Vec<(Color, Color, BBox)> = Vec::new();
+		for (indx, color) in self.colors.iter().enumerate() {
+			let p_type = self.p_types[indx];
+			let c = self.coord_at(indx);
+			if p_type == PixelType::FREE && grey_level(color) == 0 {
+				zero_weight.0 += 1;
+				zero_weight.1 = Some(zero_weight.1.map_or((c, c), |bbox| expand(bbox, c)));
+			}
+
+			// сивият под е нормален, колкото и близо да е до старта или края
+			if p_type == PixelType::START || p_type == PixelType::END || p_type == PixelType::WALL || self.palette.is_grey(color) {
+				continue;
+			}
+			for reserved in [self.palette.start, self.palette.end] {
+				if color.distance(&reserved) > NEAR_RESERVED {
+					continue;
+				}
+				match near_reserved.iter_mut().find(|(known, _, _)| known == color) {
+					Some((_, _, bbox)) => *bbox = expand(*bbox, c),
+					None => near_reserved.push((*color, reserved, (c, c)))
+				}
+			}
+		}
+
+		if let (count, Some(bbox)) = zero_weight {
+			lints.push(Lint { kind: LintKind::ZeroWeight { count }, color: Color { r: 0, g: 0, b: 0 }, bbox });
+		}
+		for (color, reserved, bbox) in near_reserved {
+			lints.push(Lint { kind: LintKind::NearReservedColor { reserved }, color, bbox });
+		}
+
+		lints
+	}
+}
@@ -1,19 +1,682 @@
-#![allow(dead_code)]
-#![allow(unused_macros)]
-#![allow(unused_imports)]
+extern crate maze;
 
-#[macro_use]
-extern crate bmp;
+use maze::{Batch, Color, Connectivity, Coord, CostModel, Diagnosis, Direction, GreyCost, KeyRules, KeySize, Lint, Maze, MazeError, MazeErrorKind, MazeResult, Palette, Portal, Schedule, Solution, Topology, TurnPenalty};
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::env;
+use std::process;
+
+const USAGE: &str = "\
+Usage: maze <command> <input> [options]
+
+Commands:
+  solve <input> [-o <output>]   find the shortest path and optionally draw it
+  validate <input>              report drawing mistakes (near-miss key sizes, doors
+                                without keys, ...) and check that the end is reachable
+  render <input> <output>       convert the maze to the format of <output>
+                                (--path draws the shortest path too)
+  info <input>                  size, format and pixel counts
+  batch <dir|glob> [-o <dir>]   solve every maze of a directory or a quoted glob pattern,
+                                the solved images mirror the inputs under <dir>
+
+Options:
+  --key-size <W>x<H>|auto|any   size of the key rectangles (default 20x20), detected
+                                from the image, or any solid rectangle with a door
+  --keys permanent|consumable   whether opening a door uses up a key of its colour
+                                (default permanent)
+  --moves 4|8|8-cut             orthogonal steps only (default), diagonal ones too
+                                unless either orthogonal cell beside the step is a
+                                wall, or any diagonal step; with diagonals costs are
+                                in hundredths
+  --grid square|hex|triangle[:<W>x<H>]
+                                cells of W x H pixels (default 1x1), hex rows are
+                                offset by half a cell; coordinates are in cells
+  --floor <file>                another floor above the input (and the floors before),
+                                repeatable; the path is written to one file per floor
+  --stair-cost <n>              cost of going one floor up or down (default 1)
+  --portal <colour>[:<cost>[:<key colour>]]
+                                areas of the colour are linked, a jump between them
+                                costs <cost> (default 1) and needs the key if given;
+                                repeatable
+  --schedule <file>             TOML or JSON file with the period and the open window
+                                of colours that are only passable on some ticks; the
+                                path may wait for them (drawn in --wait-color)
+  --cost grey|uniform[:<n>]|luminance|luminance-inverted|table:<file>
+                                cost of a step: the grey level of grey pixels and 1
+                                elsewhere (default), the same for all, the brightness
+                                (or darkness) of any colour, or a TOML or JSON table
+                                of colours and costs
+  --turn-penalty <n>            extra cost of every step that changes direction
+  --astar                       use A* instead of Dijkstra
+  --each-start                  solve: the best path from every start area on its own
+  --waypoint <row>,<col>[,<floor>]
+                                a pixel the path has to pass before the end, repeatable
+  --palette <file>              TOML or JSON palette
+  --wall, --start, --end, --path-color, --wait-color, --checkpoint, --stair <colour>
+                                override a palette colour (#rrggbb or r,g,b)
+  --conveyor-up, --conveyor-right, --conveyor-down, --conveyor-left <colour>
+                                one-way tiles, only left in their direction and never
+                                entered against it
+  --grey-tolerance <n>          channel difference still counted as grey
+  --color-tolerance <n>         channel difference still counted as the same colour
+  --json                        print the result as JSON on stdout
+  --explored <file>             solve/validate: if the end can't be reached, draw the
+                                explored area over the maze into <file>
+  --report <file>               batch: write the report to a .csv or .json file
+                                instead of stdout
+  --jobs <n>                    batch: number of threads (default: every core)
+
+Exit codes: 0 ok, 1 usage, 2 no start, 3 no end, 4 I/O error,
+5 invalid input (or validate found mistakes)";
+
+// exit codes
+const EXIT_USAGE: i32 = 1;
+const EXIT_NO_START: i32 = 2;
+const EXIT_NO_END: i32 = 3;
+const EXIT_IO: i32 = 4;
+const EXIT_INVALID: i32 = 5;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Command {
+	Solve,
+	Validate,
+	Render,
+	Info,
+	Batch,
+}
+
+struct Options {
+	command: Command,
+	input: String,
+	floors: Vec<String>,
+	output: Option<String>,
+	key_size: KeySize,
+	key_rules: KeyRules,
+	connectivity: Connectivity,
+	topology: Topology,
+	stair_cost: Option<u32>,
+	portals: Vec<Portal>,
+	schedules: Vec<Schedule>,
+	cost_model: Arc<dyn CostModel>,
+	palette: Palette,
+	astar: bool,
+	each_start: bool,
+	waypoints: Vec<Coord>,
+	path: bool,
+	json: bool,
+	report: Option<String>,
+	jobs: usize,
+	explored: Option<String>,
+}
+
+// why the arguments were rejected: a mistake in them, or a side-car file that couldn't be read
+enum ArgsError {
+	Usage(String),
+	Io(MazeError),
+}
+
+// a side-car file that can't be read exits like an input that can't, a bad one like a bad option
+impl From<MazeError> for ArgsError {
+	fn from(e: MazeError) -> ArgsError {
+		match e.kind {
+			MazeErrorKind::Io(_) => ArgsError::Io(e),
+			_ => ArgsError::Usage(e.to_string())
+		}
+	}
+}
+
+impl From<String> for ArgsError {
+	fn from(e: String) -> ArgsError {
+		ArgsError::Usage(e)
+	}
+}
+
+impl From<&str> for ArgsError {
+	fn from(e: &str) -> ArgsError {
+		ArgsError::Usage(e.to_string())
+	}
+}
+
+fn parse_args(args: &[String]) -> Result<Options, ArgsError> {
+	let command = match args.first().map(|arg| arg.as_str()) {
+		Some("solve") => Command::Solve,
+		Some("validate") => Command::Validate,
+		Some("render") => Command::Render,
+		Some("info") => Command::Info,
+		Some("batch") => Command::Batch,
+		Some(other) => return Err(format!("Unknown command '{}'", other).into()),
+		None => return Err("Missing command".into())
+	};
+
+	let mut options = Options {
+		command,
+		input: String::new(),
+		floors: Vec::new(),
+		output: None,
+		key_size: KeySize::default(),
+		key_rules: KeyRules::default(),
+		connectivity: Connectivity::default(),
+		topology: Topology::default(),
+		stair_cost: None,
+		portals: Vec::new(),
+		schedules: Vec::new(),
+		cost_model: Arc::new(GreyCost),
+		palette: Palette::default(),
+		astar: false,
+		each_start: false,
+		waypoints: Vec::new(),
+		path: false,
+		json: false,
+		report: None,
+		jobs: 0,
+		explored: None,
+	};
+
+	// опциите на палитрата се прилагат след файла с палитра, независимо от реда им
+	let mut palette_file: Option<String> = None;
+	let mut overrides: Vec<(String, String)> = Vec::new();
+	let mut positional: Vec<String> = Vec::new();
+	let mut turn_penalty: u32 = 0;
+
+	let mut args = args.iter().skip(1);
+	while let Some(arg) = args.next() {
+		let mut value = |name: &str| args.next().cloned().ok_or(format!("Missing value for {}", name));
+
+		match arg.as_str() {
+			"-o" | "--output" => options.output = Some(value(arg)?),
+			"--key-size" => options.key_size = value(arg)?.parse().map_err(|e: MazeError| e.details)?,
+			"--keys" => options.key_rules = value(arg)?.parse().map_err(|e: MazeError| e.details)?,
+			"--moves" => options.connectivity = value(arg)?.parse().map_err(|e: MazeError| e.details)?,
+			"--grid" => options.topology = value(arg)?.parse().map_err(|e: MazeError| e.details)?,
+			"--floor" => options.floors.push(value(arg)?),
+			"--stair-cost" => {
+				let cost = value(arg)?;
+				options.stair_cost = Some(cost.parse().map_err(|_| format!("Expected a number for --stair-cost, found '{}'", cost))?);
+			},
+			"--portal" => options.portals.push(value(arg)?.parse().map_err(|e: MazeError| e.details)?),
+			"--schedule" => options.schedules = Schedule::load(&value(arg)?)?,
+			"--cost" => options.cost_model = maze::parse_cost_model(&value(arg)?)?,
+			"--turn-penalty" => {
+				let penalty = value(arg)?;
+				turn_penalty = penalty.parse().map_err(|_| format!("Expected a number for --turn-penalty, found '{}'", penalty))?;
+			},
+			"--astar" => options.astar = true,
+			"--each-start" => options.each_start = true,
+			"--waypoint" => {
+				let waypoint = value(arg)?;
+				let numbers: Vec<Option<u32>> = waypoint.split(',').map(|n| n.trim().parse().ok()).collect();
+				let coord = match numbers.as_slice() {
+					[Some(row), Some(col)] => Some(Coord::new(*row, *col)),
+					[Some(row), Some(col), Some(floor)] => Some(Coord::on_floor(*row, *col, *floor)),
+					_ => None
+				};
+				options.waypoints.push(coord.ok_or(format!("Expected a waypoint as row,col or row,col,floor, found '{}'", waypoint))?);
+			},
+			"--path" => options.path = true,
+			"--json" => options.json = true,
+			"--report" => options.report = Some(value(arg)?),
+			"--explored" => options.explored = Some(value(arg)?),
+			"--jobs" => {
+				let jobs = value(arg)?;
+				options.jobs = jobs.parse().map_err(|_| format!("Expected a number for --jobs, found '{}'", jobs))?;
+			},
+			"--palette" => palette_file = Some(value(arg)?),
+			"--wall" | "--start" | "--end" | "--path-color" | "--wait-color" | "--checkpoint" | "--stair" | "--grey-tolerance" | "--color-tolerance"
+			| "--conveyor-up" | "--conveyor-right" | "--conveyor-down" | "--conveyor-left" => {
+				overrides.push((arg.clone(), value(arg)?));
+			},
+			_ if arg.starts_with('-') => return Err(format!("Unknown option '{}'", arg).into()),
+			_ => positional.push(arg.clone())
+		}
+	}
+
+	if turn_penalty > 0 {
+		options.cost_model = Arc::new(TurnPenalty { base: options.cost_model, penalty: turn_penalty });
+	}
+	if let Some(file_name) = palette_file {
+		options.palette = Palette::load(&file_name)?;
+	}
+	for (name, value) in overrides {
+		let palette = &mut options.palette;
+		match name.as_str() {
+			"--grey-tolerance" | "--color-tolerance" => {
+				let tolerance = value.parse::<u8>().map_err(|_| format!("Expected a number in [0, 255] for {}, found '{}'", name, value))?;
+				match name.as_str() {
+					"--grey-tolerance" => palette.tolerance.grey = tolerance,
+					_ => palette.tolerance.color = tolerance
+				}
+			},
+			_ => {
+				let color = value.parse::<Color>().map_err(|e| e.to_string())?;
+				match name.as_str() {
+					"--wall" => palette.wall = color,
+					"--start" => palette.start = color,
+					"--end" => palette.end = color,
+					"--checkpoint" => palette.checkpoint = Some(color),
+					"--stair" => palette.stair = Some(color),
+					"--wait-color" => palette.wait = color,
+					conveyor if conveyor.starts_with("--conveyor-") => {
+						let direction: Direction = conveyor["--conveyor-".len()..].parse().map_err(|e: MazeError| e.details)?;
+						palette.conveyors[direction as usize] = Some(color);
+					},
+					_ => palette.path = color
+				}
+			}
+		}
+	}
+
+	let mut positional = positional.into_iter();
+	options.input = positional.next().ok_or("Missing input file")?;
+	if command == Command::Render {
+		options.output = Some(positional.next().ok_or("Missing output file")?);
+	}
+	if let Some(extra) = positional.next() {
+		return Err(format!("Unexpected argument '{}'", extra).into());
+	}
+
+	Ok(options)
+}
+
+fn exit_code(e: &MazeError) -> i32 {
+	match e.kind {
+		MazeErrorKind::NoStart => EXIT_NO_START,
+		MazeErrorKind::NoEnd | MazeErrorKind::Unreachable => EXIT_NO_END,
+		MazeErrorKind::Io(_) => EXIT_IO,
+		_ => EXIT_INVALID
+	}
+}
+
+// the "status" of a failed command in the JSON output
+fn status(e: &MazeError) -> &'static str {
+	match e.kind {
+		MazeErrorKind::CoordOutOfRange => "CoordOutOfRange",
+		MazeErrorKind::NoStart => "NoStart",
+		MazeErrorKind::NoEnd => "NoEnd",
+		MazeErrorKind::MultipleStarts => "MultipleStarts",
+		MazeErrorKind::Unreachable => "Unreachable",
+		MazeErrorKind::MalformedKey { .. } => "MalformedKey",
+		MazeErrorKind::AmbiguousKeySize => "AmbiguousKeySize",
+		MazeErrorKind::Config => "Config",
+		MazeErrorKind::Io(_) => "Io",
+		MazeErrorKind::Decode => "Decode",
+		MazeErrorKind::InternalInvariant => "InternalInvariant",
+		MazeErrorKind::Other => "Other"
+	}
+}
+
+// (first step, number of ticks) of every run of waits on one pixel
+fn wait_runs(solution: &Solution) -> Vec<(usize, usize)> {
+	let mut runs: Vec<(usize, usize)> = Vec::new();
+	for step in solution.waits() {
+		match runs.last_mut() {
+			Some((first, ticks)) if *first + *ticks == step => *ticks += 1,
+			_ => runs.push((step, 1))
+		}
+	}
+	runs
+}
+
+// coords are [row, col], or [row, col, floor] on a path that leaves floor 0
+fn solution_json(solution: &Solution) -> Value {
+	let floors = solution.coords().any(|c| c.floor > 0);
+	let coord = |i: usize| {
+		let c = solution.steps[i].coord;
+		match floors {
+			true => json!([c.row, c.col, c.floor]),
+			false => json!([c.row, c.col])
+		}
+	};
+	let events = |events: &[(Color, usize)]| events.iter()
+		.map(|(color, step)| json!({"color": color.to_string(), "step": step}))
+		.collect::<Vec<Value>>();
+
+	let checkpoints: Vec<Value> = solution.checkpoints.iter()
+		.map(|(checkpoint, step)| json!({"checkpoint": checkpoint, "step": step}))
+		.collect();
+
+	let waits: Vec<Value> = wait_runs(solution).iter()
+		.map(|(step, ticks)| json!({"step": step, "ticks": ticks}))
+		.collect();
+
+	json!({
+		"cost": solution.cost,
+		"length": solution.len(),
+		"start_area": solution.start,
+		"start": coord(0),
+		"end": coord(solution.len() - 1),
+		"keys": events(&solution.pickups),
+		"doors": events(&solution.openings),
+		"checkpoints": checkpoints,
+		"portals": events(&solution.jumps),
+		"waits": waits,
+	})
+}
+
+// the fields of the objects one after another in a single object
+fn merge(objects: Vec<Value>) -> Value {
+	let mut fields = Map::new();
+	for object in objects {
+		if let Value::Object(object) = object {
+			fields.extend(object);
+		}
+	}
+	Value::Object(fields)
+}
+
+// "(row, col)", with the floor for the ones above floor 0
+fn coord_text(c: &Coord) -> String {
+	match c.floor {
+		0 => format!("({}, {})", c.row, c.col),
+		floor => format!("({}, {}, floor {})", c.row, c.col, floor)
+	}
+}
+
+fn solution_text(solution: &Solution) -> String {
+	let coord = |step: usize| coord_text(&solution.steps[step].coord);
+	let mut text = format!("cost: {}\nlength: {}\nstart: area {} {}", solution.cost, solution.len(), solution.start, coord(0));
+	for (color, step) in &solution.pickups {
+		text.push_str(&format!("\nkey {} at step {} {}", color, step, coord(*step)));
+	}
+	for (color, step) in &solution.openings {
+		text.push_str(&format!("\ndoor {} opened at step {} {}", color, step, coord(*step)));
+	}
+	for (checkpoint, step) in &solution.checkpoints {
+		text.push_str(&format!("\ncheckpoint {} at step {} {}", checkpoint, step, coord(*step)));
+	}
+	for (color, step) in &solution.jumps {
+		text.push_str(&format!("\nportal {} from {} to {} at step {}", color, coord(*step - 1), coord(*step), step));
+	}
+	for (step, ticks) in wait_runs(solution) {
+		text.push_str(&format!("\nwait {} ticks at {} from tick {}", ticks, coord(step), solution.steps[step - 1].time));
+	}
+	text
+}
+
+fn diagnosis_json(diagnosis: &Diagnosis) -> Value {
+	let colors = |colors: &[Color]| colors.iter().map(|color| color.to_string()).collect::<Vec<String>>();
+	let (min, max) = diagnosis.reachable;
+	json!({
+		"reason": format!("{:?}", diagnosis.reason),
+		"blocking_doors": colors(&diagnosis.blocking_doors),
+		"doors_without_key": colors(&diagnosis.doors_without_key),
+		"reachable": [[min.row, min.col], [max.row, max.col]],
+	})
+}
+
+fn diagnosis_text(diagnosis: &Diagnosis) -> String {
+	let colors = |colors: &[Color]| colors.iter().map(|color| color.to_string()).collect::<Vec<String>>().join(" ");
+	let (min, max) = diagnosis.reachable;
+	format!("reason: {:?}\nblocking doors: {}\ndoors without key: {}\nreachable: ({}, {}) - ({}, {})",
+		diagnosis.reason, colors(&diagnosis.blocking_doors), colors(&diagnosis.doors_without_key), min.row, min.col, max.row, max.col)
+}
+
+// {"lints": [...]} to merge into a JSON object
+fn lints_json(lints: &[Lint]) -> Value {
+	let lints: Vec<Value> = lints.iter().map(|lint| {
+		let (min, max) = lint.bbox;
+		json!({
+			"kind": format!("{:?}", lint.kind),
+			"color": lint.color.to_string(),
+			"bbox": [[min.row, min.col], [max.row, max.col]],
+			"message": lint.to_string(),
+		})
+	}).collect();
+	json!({"lints": lints})
+}
+
+// The linter needs one key size. Keys of any size are checked against the most common one.
+fn lint_key_size(maze: &mut Maze, key_size: KeySize) -> MazeResult<(u32, u32)> {
+	match key_size {
+		KeySize::Fixed { height, width } => Ok((height, width)),
+		KeySize::Auto => Ok(maze.detect_key_size()?.unwrap_or((0, 0))),
+		KeySize::Any => Ok(maze.key_size_candidates().first().map_or((0, 0), |candidate| (candidate.height, candidate.width)))
+	}
+}
+
+// the input, with the --floor files stacked on top of it
+fn open_maze(options: &Options) -> MazeResult<Maze> {
+	let mut floors: Vec<Maze> = vec![Maze::open_with_palette(&options.input, options.palette)?];
+	for file_name in &options.floors {
+		floors.push(Maze::open_with_palette(file_name, options.palette)?);
+	}
+	match floors.len() {
+		1 => Ok(floors.remove(0)),
+		_ => Maze::stack(floors)
+	}
+}
+
+fn solve(maze: &mut Maze, options: &Options) -> MazeResult<Solution> {
+	maze.find_path_with(options.key_size, options.astar)?;
+	maze.solution()
+}
+
+// solve --each-start: one result per start area, exits with 0 if any of them reaches the end
+fn run_each_start(maze: &mut Maze, options: &Options) -> MazeResult<i32> {
+	let results = maze.solve_each_start(options.key_size, options.astar)?;
+	let solutions: Vec<Solution> = results.iter().filter_map(|result| result.as_ref().ok()).cloned().collect();
+	if let Some(output) = &options.output {
+		maze.save_paths(&solutions, output)?;
+	}
+
+	match options.json {
+		true => {
+			let starts: Vec<Value> = results.iter().enumerate().map(|(area, result)| match result {
+				Ok(solution) => merge(vec![json!({"status": "Solved"}), solution_json(solution)]),
+				Err(e) => json!({"status": status(e), "start_area": area, "error": e.to_string()})
+			}).collect();
+			println!("{}", json!({"file": options.input, "starts": starts}));
+		},
+		false => {
+			let texts: Vec<String> = results.iter().enumerate().map(|(area, result)| match result {
+				Ok(solution) => solution_text(solution),
+				Err(e) => format!("start: area {}\n{}", area, e)
+			}).collect();
+			println!("{}", texts.join("\n\n"));
+		}
+	}
+
+	match results.iter().find(|result| result.is_ok()) {
+		Some(_) => Ok(0),
+		None => Ok(results.first().and_then(|result| result.as_ref().err()).map_or(EXIT_NO_END, exit_code))
+	}
+}
+
+// The report goes to the --report file or to stdout, as JSON for .json files and --json.
+// Files that fail don't change the exit code, they are listed in the report.
+fn run_batch(options: &Options) -> i32 {
+	let batch = Batch {
+		palette: options.palette,
+		key_size: options.key_size,
+		key_rules: options.key_rules,
+		connectivity: options.connectivity,
+		topology: options.topology,
+		portals: options.portals.clone(),
+		schedules: options.schedules.clone(),
+		cost_model: Some(options.cost_model.clone()),
+		astar: options.astar,
+		output_dir: options.output.as_ref().map(PathBuf::from),
+		jobs: options.jobs,
+	};
+
+	let entries = match batch.run(&options.input) {
+		Ok(entries) => entries,
+		Err(e) => {
+			eprintln!("{}: {}", options.input, e);
+			return exit_code(&e);
+		}
+	};
+
+	let as_json = match &options.report {
+		Some(file_name) => file_name.to_ascii_lowercase().ends_with(".json"),
+		None => options.json
+	};
+	let report = match as_json {
+		true => maze::report_json(&entries),
+		false => maze::report_csv(&entries)
+	};
+
+	match &options.report {
+		Some(file_name) => {
+			if let Err(e) = fs::write(file_name, report) {
+				eprintln!("Failed to write {}: {}", file_name, e);
+				return EXIT_IO;
+			}
+		},
+		None => print!("{}", report)
+	}
+
+	0
+}
+
+// prints the result of the command and returns the exit code
+fn run(options: &Options) -> i32 {
+	if options.command == Command::Batch {
+		return run_batch(options);
+	}
+
+	let report_error = |e: &MazeError| {
+		match options.json {
+			true => println!("{}", json!({"file": options.input, "status": status(e), "error": e.to_string()})),
+			false => eprintln!("{}: {}", options.input, e)
+		}
+		exit_code(e)
+	};
+
+	let mut maze = match open_maze(options) {
+		Ok(maze) => maze,
+		Err(e) => return report_error(&e)
+	};
+	if let Some(stair_cost) = options.stair_cost {
+		maze.set_stair_cost(stair_cost);
+	}
+	maze.set_key_rules(options.key_rules);
+	maze.set_connectivity(options.connectivity);
+	if let Err(e) = maze.set_topology(options.topology) {
+		return report_error(&e);
+	}
+	maze.set_waypoints(options.waypoints.clone());
+	maze.set_portals(options.portals.clone());
+	maze.set_schedules(options.schedules.clone());
+	maze.set_cost_model(options.cost_model.clone());
+
+	match options.command {
+		Command::Solve if options.each_start => {
+			return run_each_start(&mut maze, options).unwrap_or_else(|e| report_error(&e));
+		},
+		Command::Solve | Command::Validate => {
+			let lints: Vec<Lint> = match options.command {
+				Command::Validate => match lint_key_size(&mut maze, options.key_size) {
+					Ok((key_height, key_width)) => maze.validate(key_height, key_width),
+					Err(e) => return report_error(&e)
+				},
+				_ => Vec::new()
+			};
+			let lints_field = match options.command {
+				Command::Validate => lints_json(&lints),
+				_ => json!({})
+			};
+			if !options.json {
+				for lint in &lints {
+					eprintln!("{}: warning: {}", options.input, lint);
+				}
+			}
+
+			let solution = match solve(&mut maze, options) {
+				Ok(solution) => solution,
+				Err(e) => {
+					let diagnosis = match (maze.diagnose(), options.json) {
+						(Some(diagnosis), _) => diagnosis,
+						(None, true) => {
+							println!("{}", merge(vec![json!({"file": options.input, "status": status(&e), "error": e.to_string()}), lints_field]));
+							return exit_code(&e);
+						},
+						(None, false) => return report_error(&e)
+					};
+
+					if let Some(explored) = &options.explored {
+						if let Err(e) = maze.save_diagnosis(&diagnosis, explored) {
+							return report_error(&e);
+						}
+					}
+
+					match options.json {
+						true => println!("{}", merge(vec![
+							json!({"file": options.input, "status": status(&e), "error": e.to_string(), "diagnosis": diagnosis_json(&diagnosis)}),
+							lints_field
+						])),
+						false => eprintln!("{}: {}\n{}", options.input, e, diagnosis_text(&diagnosis))
+					}
+					return exit_code(&e);
+				}
+			};
+
+			if let Some(output) = &options.output {
+				if let Err(e) = maze.save_path(output) {
+					return report_error(&e);
+				}
+			}
+
+			match (options.json, options.command) {
+				(true, _) => println!("{}", merge(vec![json!({"file": options.input, "status": "Solved"}), solution_json(&solution), lints_field])),
+				(false, Command::Validate) => println!("{}: the end is reachable at cost {}, {} warnings", options.input, solution.cost, lints.len()),
+				(false, _) => println!("{}", solution_text(&solution))
+			}
+
+			if !lints.is_empty() {
+				return EXIT_INVALID;
+			}
+		},
+		Command::Render => {
+			let output = options.output.as_ref().unwrap();
+			let saved = match options.path {
+				true => solve(&mut maze, options).and_then(|_| maze.save_path(output)),
+				false => maze.save(output)
+			};
+			if let Err(e) = saved {
+				return report_error(&e);
+			}
+
+			if options.json {
+				println!("{}", json!({"file": options.input, "status": "Rendered", "output": output}));
+			}
+		},
+		Command::Info => {
+			let info = maze.info();
+			let colors: Vec<String> = info.colors.iter().map(|color| color.to_string()).collect();
+			match options.json {
+				true => println!("{}", json!({
+					"file": options.input, "format": format!("{:?}", info.format), "width": info.width, "height": info.height, "floors": info.floors,
+					"walls": info.walls, "free": info.free, "start": info.start, "end": info.end, "colored": info.colored, "colors": colors,
+				})),
+				false => println!("format: {:?}\nsize: {}x{}\nfloors: {}\nwalls: {}\nfree: {}\nstart: {}\nend: {}\nkeys and doors: {} ({})",
+					info.format, info.width, info.height, info.floors, info.walls, info.free, info.start, info.end, info.colored, colors.join(" "))
+			}
+		},
+		Command::Batch => unreachable!()
+	}
+
+	0
+}
 
-extern crate maze;
-use maze::Maze;
-use std::rc::Rc;
 fn main() {
-    let img = bmp::open("./inputs/01.maze20x20.bmp").unwrap_or_else(|e| {
-		panic!("Failed to open: {}", e);
+	let args: Vec<String> = env::args().skip(1).collect();
+	if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+		println!("{}", USAGE);
+		return;
+	}
+
+	let options = parse_args(&args).unwrap_or_else(|e| match e {
+		ArgsError::Usage(e) => {
+			eprintln!("{}\n\n{}", e, USAGE);
+			process::exit(EXIT_USAGE);
+		},
+		ArgsError::Io(e) => {
+			eprintln!("{}", e);
+			process::exit(exit_code(&e));
+		}
 	});
-	
-	let mut maze: Maze = Maze::from(&img);
-	let _ = maze.find_path(20, 20);
-	let _ = maze.save_path("./outputs/01.maze20x20.bmp");
-}
\ No newline at end of file
+
+	process::exit(run(&options));
+}
@@ -0,0 +1,164 @@
+use std::fs;
+use std::str::FromStr;
+
+use super::{Color, Direction, MazeError, MazeResult, PixelType};
+use super::{WALL_COLOR, START_COLOR, END_COLOR, PATH_COLOR, WAIT_COLOR};
+use super::config::{self, config_error};
+
+// How far from exact a colour may be and still match. Both are the biggest allowed
+// difference in a single channel, 0 means exact matching.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Tolerance {
+	// between the channels of a pixel that still counts as grey floor
+	pub grey: u8,
+	// from the wall colour (greys this dark are walls too, not floor of that weight), from the start
+	// and end colours (exactly grey pixels stay floor), between the key and door colours and
+	// between the pixels of one coloured area
+	pub color: u8,
+}
+
+// colours used to classify the pixels of a maze and to draw the found path
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette {
+	pub wall: Color,
+	pub start: Color,
+	pub end: Color,
+	pub path: Color,
+	// the pixels of the path where it waits for a scheduled area to open
+	pub wait: Color,
+	// areas of this colour have to be passed before the end, None has no checkpoint areas
+	pub checkpoint: Option<Color>,
+	// cells of this colour lead to the same cell on the floor above and below, None has no stairs
+	pub stair: Option<Color>,
+	// one-way tiles pointing up, right, down and left (in the order of Direction::ALL), None has no such tiles
+	pub conveyors: [Option<Color>; 4],
+	pub tolerance: Tolerance,
+}
+
+impl Default for Palette {
+	fn default() -> Self {
+		Palette {
+			wall: WALL_COLOR,
+			start: START_COLOR,
+			end: END_COLOR,
+			path: PATH_COLOR,
+			wait: WAIT_COLOR,
+			checkpoint: None,
+			stair: None,
+			conveyors: [None; 4],
+			tolerance: Tolerance::default(),
+		}
+	}
+}
+
+impl Palette {
+	pub fn matches(&self, color: &Color, other: &Color) -> bool {
+		color.distance(other) <= self.tolerance.color
+	}
+
+	pub fn is_grey(&self, color: &Color) -> bool {
+		let max = color.r.max(color.g).max(color.b);
+		let min = color.r.min(color.g).min(color.b);
+		max - min <= self.tolerance.grey
+	}
+
+	// exactly the wall, start, end, checkpoint, stair or a conveyor colour
+	fn is_reserved(&self, color: &Color) -> bool {
+		[Some(self.wall), Some(self.start), Some(self.end), self.checkpoint, self.stair].iter()
+			.chain(self.conveyors.iter())
+			.any(|reserved| *reserved == Some(*color))
+	}
+
+	// Type of a pixel judged only by its colour - KEY and ZONE are told apart by the area shape.
+	// Past the wall (anti-aliased wall edges are dark greys), an exactly grey pixel is floor unless
+	// it is exactly one of the reserved colours, so the colour tolerance doesn't turn the floor
+	// next to the almost grey start and end into them.
+	pub(crate) fn classify(&self, color: &Color) -> PixelType {
+		if self.matches(color, &self.wall) { PixelType::WALL }
+		else if color.r == color.g && color.g == color.b && !self.is_reserved(color) { PixelType::FREE }
+		else if self.matches(color, &self.start) { PixelType::START }
+		else if self.matches(color, &self.end) { PixelType::END }
+		else if self.checkpoint.is_some_and(|checkpoint| self.matches(color, &checkpoint)) { PixelType::CHECKPOINT }
+		else if self.stair.is_some_and(|stair| self.matches(color, &stair)) { PixelType::STAIR }
+		else if self.conveyor(color).is_some() { PixelType::CONVEYOR }
+		else if self.is_grey(color) { PixelType::FREE }
+		else { PixelType::ZONE }
+	}
+
+	// the direction of a conveyor of this colour
+	pub fn conveyor(&self, color: &Color) -> Option<Direction> {
+		Direction::ALL.iter()
+			.zip(&self.conveyors)
+			.find(|(_, conveyor)| conveyor.is_some_and(|conveyor| self.matches(color, &conveyor)))
+			.map(|(direction, _)| *direction)
+	}
+
+	// Reads a palette from a TOML or JSON file. Colours are given as [r, g, b] or "#rrggbb",
+	// either at the top level or in a [palette] table; missing ones keep their default.
+	// Tolerances go in a [tolerance] table as grey = n and color = n.
+	pub fn load(file_name: &str) -> MazeResult<Palette> {
+		let text = fs::read_to_string(file_name).map_err(|e| MazeError::from(e).context(format!("Failed to read {}", file_name)))?;
+
+		text.parse()
+	}
+}
+
+impl FromStr for Palette {
+	type Err = MazeError;
+
+	fn from_str(text: &str) -> MazeResult<Palette> {
+		let mut palette = Palette::default();
+
+		for (key, value) in config::parse(text)? {
+			if let Some(name) = key.strip_prefix("tolerance.") {
+				let tolerance = match name {
+					"grey" | "gray" => &mut palette.tolerance.grey,
+					"color" | "colour" => &mut palette.tolerance.color,
+					_ => return Err(config_error(format!("Unknown tolerance '{}'", name)))
+				};
+
+				*tolerance = value.as_u32().filter(|n| *n <= 255).ok_or_else(|| config_error(
+					format!("Expected a number in [0, 255] for '{}', found {:?}", key, value)
+				))? as u8;
+				continue;
+			}
+
+			let name = match key.strip_prefix("palette.") {
+				Some(name) => name,
+				None if !key.contains('.') => key.as_str(),
+				// други секции от общия файл
+				None => continue
+			};
+
+			if name == "checkpoint" || name == "stair" || name.starts_with("conveyor_") {
+				let color = Some(value.as_color().ok_or_else(|| config_error(
+					format!("Expected [r, g, b] or \"#rrggbb\" for '{}', found {:?}", key, value)
+				))?);
+				match name {
+					"checkpoint" => palette.checkpoint = color,
+					"stair" => palette.stair = color,
+					_ => {
+						let direction: Direction = name["conveyor_".len()..].parse()?;
+						palette.conveyors[direction as usize] = color;
+					}
+				}
+				continue;
+			}
+
+			let color = match name {
+				"wall" => &mut palette.wall,
+				"start" => &mut palette.start,
+				"end" => &mut palette.end,
+				"path" => &mut palette.path,
+				"wait" => &mut palette.wait,
+				_ => return Err(config_error(format!("Unknown palette colour '{}'", name)))
+			};
+
+			*color = value.as_color().ok_or_else(|| config_error(
+				format!("Expected [r, g, b] or \"#rrggbb\" for '{}', found {:?}", key, value)
+			))?;
+		}
+
+		Ok(palette)
+	}
+}
@@ -0,0 +1,126 @@
+// Portals link distant areas of the maze. Every area of a portal colour is an entrance:
+// the walker steps onto it like onto a door and from anywhere on it can jump to any other
+// area of the same colour, at the fixed cost of the portal. It comes out on the first pixel
+// (in row-major order) of that area. A portal can be locked, then it only works while
+// a key of its key colour is held; the key is not spent on it.
+
+use std::collections::hash_map::Entry;
+use std::fmt;
+use std::str::FromStr;
+
+use super::{Color, Coord, Maze, MazeError, MazeResult, Move, PixelType, NO_REGION};
+use super::config::config_error;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Portal {
+	pub color: Color,
+	// cost of a jump, scaled like the weight of a straight step
+	pub cost: u32,
+	// colour of the key needed to use the portal, None if it is always open
+	pub key: Option<Color>,
+}
+
+impl Portal {
+	pub fn new(color: Color, cost: u32) -> Self {
+		Portal { color, cost, key: None }
+	}
+
+	pub fn locked(color: Color, cost: u32, key: Color) -> Self {
+		Portal { color, cost, key: Some(key) }
+	}
+}
+
+// "<colour>", "<colour>:<cost>" or "<colour>:<cost>:<key colour>", the cost is 1 if left out
+impl FromStr for Portal {
+	type Err = MazeError;
+
+	fn from_str(text: &str) -> MazeResult<Portal> {
+		let parts: Vec<&str> = text.split(':').collect();
+		let cost = match parts.get(1) {
+			Some(cost) => cost.trim().parse().map_err(|_| config_error(format!("Expected a number for the cost of the portal '{}'", text)))?,
+			None => 1
+		};
+		match parts.as_slice() {
+			[color] | [color, _] => Ok(Portal::new(color.parse()?, cost)),
+			[color, _, key] => Ok(Portal::locked(color.parse()?, cost, key.parse()?)),
+			_ => Err(config_error(format!("Expected a portal as colour[:cost[:key colour]], found '{}'", text)))
+		}
+	}
+}
+
+impl fmt::Display for Portal {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		write!(fmt, "{}:{}", self.color, self.cost)?;
+		if let Some(key) = self.key {
+			write!(fmt, ":{}", key)?;
+		}
+		Ok(())
+	}
+}
+
+impl Maze {
+	// the portals used by the next search
+	pub fn set_portals(&mut self, portals: Vec<Portal>) {
+		self.portals = portals;
+	}
+
+	pub fn portals(&self) -> &[Portal] {
+		&self.portals
+	}
+
+	// number of the portal with this colour
+	pub(crate) fn portal_of(&self, color: &Color) -> Option<usize> {
+		self.portals.iter().position(|portal| self.palette.matches(&portal.color, color))
+	}
+
+	// Classifies every portal area and remembers where each of them comes out.
+	pub(crate) fn find_portals(&mut self) {
+		if self.portals.is_empty() {
+			return;
+		}
+
+		self.portal_exits = vec![Vec::new(); self.portals.len()];
+		for indx in 0..self.colors.len() {
+			let portal = match (self.palette.classify(&self.colors[indx]), self.portal_of(&self.colors[indx])) {
+				(PixelType::ZONE, Some(portal)) => portal,
+				_ => continue
+			};
+			if self.region_ids[indx] == NO_REGION {
+				let c = self.coord_at(indx);
+				self.set_area_at(&c);
+			}
+
+			// пикселите се обхождат по редове, така че първият срещнат е изходът на областта
+			let region_id = self.region_ids[indx];
+			if let Entry::Vacant(entry) = self.portal_regions.entry(region_id) {
+				entry.insert(portal);
+				self.portal_exits[portal].push((region_id, indx));
+			}
+		}
+	}
+
+	// the exits of the other areas of the portal the pixel is on, none if it isn't on one
+	pub(crate) fn portal_jumps(&self, indx: usize) -> Vec<(Option<Coord>, Move)> {
+		let region_id = self.region_ids[indx];
+		match self.portal_regions.get(&region_id) {
+			Some(portal) => self.portal_exits[*portal].iter()
+				.filter(|(id, _)| *id != region_id)
+				.map(|(_, exit)| (Some(self.coord_at(*exit)), Move::Portal(*portal)))
+				.collect(),
+			None => Vec::new()
+		}
+	}
+
+	// A lower bound of the cost to the end for paths that jump through a portal: the cheapest
+	// jump plus the estimate from the best exit. A* takes the smaller of this and its estimate,
+	// which keeps it admissible. None if there is no portal to jump through.
+	pub(crate) fn portal_bound(&self, estimate: impl Fn(&Coord) -> u32) -> Option<u32> {
+		let cost = self.portals.iter().map(|portal| self.topology.connectivity(self.connectivity).step_cost(portal.cost, false)).min()?;
+		let exit = self.portal_exits.iter()
+			.filter(|exits| exits.len() > 1)
+			.flat_map(|exits| exits.iter())
+			.map(|(_, exit)| estimate(&self.coord_at(*exit)))
+			.min()?;
+		Some(cost.saturating_add(exit))
+	}
+}
@@ -0,0 +1,103 @@
+// Optional rules that change how the search moves through a maze.
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::{MazeError, MazeResult};
+use super::config::config_error;
+
+// what happens to a key when it opens a door
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeyRules {
+	// a key opens every door of its colour, as many times as needed
+	#[default]
+	Permanent,
+	// Entering a door area spends one key of its colour and the door stays open after that.
+	// Every key area can be picked up once, so several keys of a colour add up.
+	Consumable,
+}
+
+impl FromStr for KeyRules {
+	type Err = MazeError;
+
+	fn from_str(text: &str) -> MazeResult<KeyRules> {
+		match text.trim().to_ascii_lowercase().as_str() {
+			"permanent" => Ok(KeyRules::Permanent),
+			"consumable" => Ok(KeyRules::Consumable),
+			_ => Err(config_error(format!("Expected permanent or consumable keys, found '{}'", text)))
+		}
+	}
+}
+
+impl fmt::Display for KeyRules {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			KeyRules::Permanent => write!(fmt, "permanent"),
+			KeyRules::Consumable => write!(fmt, "consumable"),
+		}
+	}
+}
+
+// (row, col) offsets of the neighbours
+const STRAIGHT_STEPS: [(i32, i32); 4] = [(-1, 0), (0, -1), (0, 1), (1, 0)];
+const ALL_STEPS: [(i32, i32); 8] = [(-1, 0), (0, -1), (0, 1), (1, 0), (-1, -1), (-1, 1), (1, -1), (1, 1)];
+
+// With diagonal moves the costs are fixed point numbers in hundredths of a straight step,
+// a diagonal step costs sqrt(2) times as much.
+pub(crate) const STRAIGHT_COST: u32 = 100;
+pub(crate) const DIAGONAL_COST: u32 = 141;
+
+// which neighbours of a pixel the path can step to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Connectivity {
+	// up, down, left and right
+	#[default]
+	Four,
+	// the diagonals too, but only if neither of the two pixels beside the step is a wall
+	Eight,
+	// the diagonals too, past walls beside the step and between two that touch at a corner
+	EightCutCorners,
+}
+
+impl Connectivity {
+	pub(crate) fn steps(self) -> &'static [(i32, i32)] {
+		match self {
+			Connectivity::Four => &STRAIGHT_STEPS,
+			_ => &ALL_STEPS
+		}
+	}
+
+	// Cost of a step onto a pixel of this weight. Only the 8-connected costs are scaled,
+	// so a 4-connected path costs the sum of its weights like it always did.
+	pub(crate) fn step_cost(self, weight: u32, diagonal: bool) -> u32 {
+		match (self, diagonal) {
+			(Connectivity::Four, _) => weight,
+			(_, false) => weight.saturating_mul(STRAIGHT_COST),
+			(_, true) => weight.saturating_mul(DIAGONAL_COST),
+		}
+	}
+}
+
+// "4", "8" or "8-cut"
+impl FromStr for Connectivity {
+	type Err = MazeError;
+
+	fn from_str(text: &str) -> MazeResult<Connectivity> {
+		match text.trim().to_ascii_lowercase().as_str() {
+			"4" => Ok(Connectivity::Four),
+			"8" => Ok(Connectivity::Eight),
+			"8-cut" => Ok(Connectivity::EightCutCorners),
+			_ => Err(config_error(format!("Expected 4, 8 or 8-cut moves, found '{}'", text)))
+		}
+	}
+}
+
+impl fmt::Display for Connectivity {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Connectivity::Four => write!(fmt, "4"),
+			Connectivity::Eight => write!(fmt, "8"),
+			Connectivity::EightCutCorners => write!(fmt, "8-cut"),
+		}
+	}
+}
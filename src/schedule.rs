@@ -0,0 +1,133 @@
+// Doors and hazards that open and close with time. Every action of the walker, a step or
+// a wait on the spot, takes one tick, and an area of a scheduled colour can only be stood on
+// while the tick modulo its period is inside its open window. The search carries the tick
+// modulo the least common multiple of all periods, after that the schedules repeat.
+//
+// The schedules are read from a side-car file keyed by colour:
+//
+//     ["#ff7f27"]
+//     period = 6
+//     open = [0, 2]    # ticks 0 and 1 of every 6
+//
+// optionally under a [schedule] prefix ([schedule."#ff7f27"]), like the palette files.
+
+use std::collections::HashMap;
+use std::fs;
+
+use super::{Color, Maze, MazeError, MazeResult};
+use super::config::{self, config_error, ConfigValue};
+
+// cost of waiting a tick, the same as stepping onto a free pixel of weight 1
+pub(crate) const WAIT_COST: u32 = 1;
+// longest cycle of ticks the search keeps a layer of distances for every tick of
+const MAX_CYCLE: u32 = 10_000;
+// most pixels over all layers of a timed search, every one keeps a distance and a link
+const MAX_TICK_CELLS: u64 = 1 << 25;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Schedule {
+	pub color: Color,
+	pub period: u32,
+	// open while open.0 <= tick % period < open.1
+	pub open: (u32, u32),
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+	match b {
+		0 => a,
+		_ => gcd(b, a % b)
+	}
+}
+
+impl Schedule {
+	pub fn new(color: Color, period: u32, open: (u32, u32)) -> Self {
+		Schedule { color, period, open }
+	}
+
+	pub fn is_open(&self, tick: u32) -> bool {
+		(self.open.0..self.open.1).contains(&(tick % self.period))
+	}
+
+	// Reads the schedules of a side-car file, see the top of this file.
+	pub fn load(file_name: &str) -> MazeResult<Vec<Schedule>> {
+		let text = fs::read_to_string(file_name).map_err(|e| MazeError::from(e).context(format!("Failed to read {}", file_name)))?;
+
+		Schedule::parse_all(&text)
+	}
+
+	// the schedules of a TOML or JSON text, in the order their colours first appear
+	pub fn parse_all(text: &str) -> MazeResult<Vec<Schedule>> {
+		let mut colors: Vec<String> = Vec::new();
+		let mut fields: HashMap<String, ConfigValue> = HashMap::new();
+		for (key, value) in config::parse(text)? {
+			let key = key.strip_prefix("schedule.").unwrap_or(&key);
+			let (color, field) = key.split_once('.').ok_or_else(|| config_error(
+				format!("Expected the period or the open window of a colour, found '{}'", key)
+			))?;
+			if field != "period" && field != "open" {
+				return Err(config_error(format!("Unknown schedule field '{}'", key)));
+			}
+			if !colors.iter().any(|known| known == color) {
+				colors.push(color.to_string());
+			}
+			fields.insert(key.to_string(), value);
+		}
+
+		colors.iter().map(|name| {
+			let color: Color = name.parse()?;
+			let period = fields.get(&format!("{}.period", name)).and_then(|value| value.as_u32()).filter(|period| *period > 0);
+			let open = match fields.get(&format!("{}.open", name)) {
+				Some(ConfigValue::List(window)) if window.len() == 2 => window[0].as_u32().zip(window[1].as_u32()),
+				_ => None
+			};
+
+			match (period, open) {
+				(Some(period), Some((from, to))) if from < to && to <= period => Ok(Schedule::new(color, period, (from, to))),
+				_ => Err(config_error(format!("Expected a period above 0 and an open window [from, to] inside it for '{}'", name)))
+			}
+		}).collect()
+	}
+}
+
+impl Maze {
+	// the schedules used by the next search
+	pub fn set_schedules(&mut self, schedules: Vec<Schedule>) {
+		self.schedules = schedules;
+	}
+
+	pub fn schedules(&self) -> &[Schedule] {
+		&self.schedules
+	}
+
+	pub(crate) fn schedule_of(&self, color: &Color) -> Option<&Schedule> {
+		self.schedules.iter().find(|schedule| self.palette.matches(&schedule.color, color))
+	}
+
+	// the number of ticks after which all schedules repeat, 1 without schedules
+	pub(crate) fn schedule_cycle(&self) -> MazeResult<u32> {
+		let mut cycle: u32 = 1;
+		for schedule in &self.schedules {
+			let lcm = (cycle as u64) * (schedule.period as u64) / gcd(cycle, schedule.period) as u64;
+			if lcm > MAX_CYCLE as u64 {
+				return Err(config_error(format!("The schedules only repeat after more than {} ticks, pick periods with more common factors.", MAX_CYCLE)));
+			}
+			cycle = lcm as u32;
+		}
+		match cycle {
+			1 => Ok(cycle),
+			_ => self.check_tick_layers(cycle as usize).map(|_| cycle)
+		}
+	}
+
+	// Fails before the search runs out of memory: a timed search keeps a layer for every tick
+	// of the cycle and every set of held keys and visited checkpoints.
+	pub(crate) fn check_tick_layers(&self, layers: usize) -> MazeResult<()> {
+		match layers as u64 * self.colors.len() as u64 > MAX_TICK_CELLS {
+			true => Err(config_error(format!(
+				"The schedules need {} layers of {} pixels, more than {} pixels in all, pick shorter periods or a smaller maze.",
+				layers, self.colors.len(), MAX_TICK_CELLS
+			))),
+			false => Ok(())
+		}
+	}
+}
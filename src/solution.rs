@@ -0,0 +1,50 @@
+use super::{Color, Coord};
+
+// one pixel of a solved route
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Step {
+	pub coord: Coord,
+	// cost from the start up to and including this pixel
+	pub dist: u32,
+	// ticks from the start, every step and every wait takes one
+	pub time: u32,
+	// colours of the keys held while standing on this pixel
+	pub keys: Vec<Color>,
+}
+
+// the route found by Maze::find_path, ordered from the start to the end
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Solution {
+	pub cost: u32,
+	// number of the START area the path leaves from, the areas are numbered
+	// in row-major order of their first pixel
+	pub start: usize,
+	pub steps: Vec<Step>,
+	// key colour and the index in steps where it was picked up
+	pub pickups: Vec<(Color, usize)>,
+	// with consumable keys: door colour and the index in steps where the door was opened
+	pub openings: Vec<(Color, usize)>,
+	// number of the checkpoint and the index in steps where it was visited
+	pub checkpoints: Vec<(usize, usize)>,
+	// portal colour and the index in steps where the path came out of it
+	pub jumps: Vec<(Color, usize)>,
+}
+
+impl Solution {
+	pub fn coords(&self) -> impl Iterator<Item = &Coord> {
+		self.steps.iter().map(|step| &step.coord)
+	}
+	
+	// indices in steps where the walker stood still, on the same pixel as the step before
+	pub fn waits(&self) -> impl Iterator<Item = usize> + '_ {
+		(1..self.steps.len()).filter(move |i| self.steps[*i].coord == self.steps[i - 1].coord)
+	}
+
+	pub fn len(&self) -> usize {
+		self.steps.len()
+	}
+	
+	pub fn is_empty(&self) -> bool {
+		self.steps.is_empty()
+	}
+}
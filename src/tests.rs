@@ -16,8 +16,8 @@ fn test_found_01_maze3x3_20x20() {
 	});
 	let mut maze: Maze = Maze::from(&img);
      assert_eq!(maze.find_path(20, 20).err(), Some(MazeError::new(
-		MazeErrorKind::NoEnd,
-		format!("There is no end zone."),
+		MazeErrorKind::Unreachable,
+		"The end can't be reached, there is no key for the doors #ffc90e, #a349a4, #ed1c24, #a954aa.",
 	)));
 }
 
@@ -40,7 +40,7 @@ fn test_found_03_maze3x3() {
 	let mut maze: Maze = Maze::from(&img);
      assert_eq!(maze.find_path(3, 3).err(), Some(MazeError::new(
 		MazeErrorKind::NoEnd,
-		format!("There is no end zone."),
+		"There is no end zone.",
 	)));
 }
 
@@ -53,7 +53,7 @@ fn test_found_04_maze3x3() {
 	let mut maze: Maze = Maze::from(&img);
     assert_eq!(maze.find_path(3, 3).err(), Some(MazeError::new(
 		MazeErrorKind::NoStart,
-		format!("There is no start."),
+		"There is no start.",
 	)));
 }
 
@@ -66,7 +66,7 @@ fn test_found_05_maze3x3() {
 	let mut maze: Maze = Maze::from(&img);
     assert_eq!(maze.find_path(3, 3).err(), Some(MazeError::new(
 		MazeErrorKind::NoStart,
-		format!("There is no start."),
+		"There is no start.",
 	)));
 }
 
@@ -88,8 +88,8 @@ fn test_found_07_maze3x3() {
 	
 	let mut maze: Maze = Maze::from(&img);
     assert_eq!(maze.find_path(3, 3).err(), Some(MazeError::new(
-		MazeErrorKind::NoEnd,
-		format!("There is no end zone."),
+		MazeErrorKind::Unreachable,
+		"The end can't be reached, there is no key for the doors #ff7f27.",
 	)));
 }
 
@@ -101,8 +101,8 @@ fn test_found_08_maze3x3() {
 	
 	let mut maze: Maze = Maze::from(&img);
      assert_eq!(maze.find_path(3, 3).err(), Some(MazeError::new(
-		MazeErrorKind::NoEnd,
-		format!("There is no end zone."),
+		MazeErrorKind::Unreachable,
+		"The end can't be reached, there is no key for the doors #a349a4, #a954aa, #ffcc1d, #ed1c24.",
 	)));
 }
 
@@ -124,8 +124,8 @@ fn test_found_01_maze20x20_3x3() {
 	
 	let mut maze: Maze = Maze::from(&img);
      assert_eq!(maze.find_path(3, 3).err(), Some(MazeError::new(
-		MazeErrorKind::NoEnd,
-		format!("There is no end zone."),
+		MazeErrorKind::Unreachable,
+		"The end can't be reached, there is no key for the doors #ed1c24, #ffc90e.",
 	)));
 }
 
@@ -137,8 +137,8 @@ fn test_found_02_maze20x20() {
 	
 	let mut maze: Maze = Maze::from(&img);
      assert_eq!(maze.find_path(20, 20).err(), Some(MazeError::new(
-		MazeErrorKind::NoEnd,
-		format!("There is no end zone."),
+		MazeErrorKind::Unreachable,
+		"The end can't be reached, there is no key for the doors #22b14c.",
 	)));
 }
 
@@ -186,11 +186,1475 @@ fn test_find_save_02_maze20x20() {
 	
 	let mut maze: Maze = Maze::from(&img);
     assert_eq!(maze.find_path(20, 20).err(), Some(MazeError::new(
-		MazeErrorKind::NoEnd,
-		format!("There is no end zone."),
+		MazeErrorKind::Unreachable,
+		"The end can't be reached, there is no key for the doors #22b14c.",
 	)));
 	assert_eq!(maze.save_path("./outputs/02.maze20x20.bmp").err(), Some(MazeError::new(
 		MazeErrorKind::NoEnd,
-		format!("There is no end zone."),
+		"There is no end zone.",
 	)));
-}
\ No newline at end of file
+}
+
+// search tests
+
+fn image_from_rows(rows: &[Vec<Color>]) -> bmp::Image {
+	let mut img = bmp::Image::new(rows[0].len() as u32, rows.len() as u32);
+	for (y, row) in rows.iter().enumerate() {
+		for (x, color) in row.iter().enumerate() {
+			img.set_pixel(x as u32, y as u32, (*color).into());
+		}
+	}
+	img
+}
+
+#[test]
+fn test_state_order() {
+	let state = |priority: u32, dist: u32, col: u32| State { priority, dist, coord: Coord::new(0, col), key_comb: 0 };
+	let mut heap: BinaryHeap<State> = vec![state(5, 1, 0), state(5, 3, 0), state(4, 0, 0)].into_iter().collect();
+	assert_eq!(heap.pop().map(|s| (s.priority, s.dist)), Some((4, 0)));
+	assert_eq!(heap.pop().map(|s| (s.priority, s.dist)), Some((5, 3)));
+	
+	// равенството е същото като наредбата
+	assert!(state(5, 1, 0) != state(5, 1, 1));
+	assert_ne!(state(5, 1, 0).cmp(&state(5, 1, 1)), Ordering::Equal);
+	assert!(state(5, 1, 0) == state(5, 1, 0));
+}
+
+#[test]
+fn test_find_cheapest_detour() {
+	let grey = |w: u8| Color { r: w, g: w, b: w };
+	let img = image_from_rows(&[
+		vec![START_COLOR, grey(200), grey(200), grey(200), END_COLOR],
+		vec![grey(1), grey(1), grey(1), grey(1), grey(1)],
+	]);
+	
+	let mut maze: Maze = Maze::from(&img);
+	assert_eq!(maze.find_path(3, 3).err(), Option::<MazeError>::None);
+	
+	let (end, comb) = maze.end.unwrap();
+	assert_eq!(end, Coord::new(0, 4));
+	assert_eq!(maze.dist_at(maze.pixel_indx(&end).unwrap(), comb), 6);
+}
+
+#[test]
+fn test_astar_same_cost_as_dijkstra() {
+	let inputs = [
+		("./inputs/01.maze3x3.bmp", 3),
+		("./inputs/02.maze3x3.bmp", 3),
+		("./inputs/03.maze3x3.bmp", 3),
+		("./inputs/06.maze3x3.bmp", 3),
+		("./inputs/01.maze20x20.bmp", 20),
+		("./inputs/02.maze20x20.bmp", 20),
+		("./inputs/03.maze20x20.bmp", 20),
+	];
+	
+	for (file_name, key_size) in inputs.iter() {
+		let img = bmp::open(file_name).unwrap_or_else(|e| {
+			panic!("Failed to open: {}", e);
+		});
+		
+		let mut dijkstra: Maze = Maze::from(&img);
+		let mut astar: Maze = Maze::from(&img);
+		assert_eq!(dijkstra.find_path(*key_size, *key_size).err(), astar.find_path_astar(*key_size, *key_size).err());
+		
+		let end_dist = |maze: &Maze| maze.end.map(|(end, comb)| maze.dist_at(maze.pixel_indx(&end).unwrap(), comb));
+		assert_eq!(end_dist(&dijkstra), end_dist(&astar), "{}", file_name);
+	}
+}
+
+#[test]
+fn test_find_path_twice() {
+	let img = bmp::open("./inputs/01.maze20x20.bmp").unwrap_or_else(|e| {
+		panic!("Failed to open: {}", e);
+	});
+	
+	let mut maze: Maze = Maze::from(&img);
+	assert_eq!(maze.find_path(3, 3).err().map(|e| e.kind), Some(MazeErrorKind::Unreachable));
+	assert_eq!(maze.find_path(20, 20).err(), Option::<MazeError>::None);
+}
+
+#[test]
+fn test_solution_cheapest_detour() {
+	let grey = |w: u8| Color { r: w, g: w, b: w };
+	let img = image_from_rows(&[
+		vec![START_COLOR, grey(200), grey(200), grey(200), END_COLOR],
+		vec![grey(1), grey(1), grey(1), grey(1), grey(1)],
+	]);
+	
+	let mut maze: Maze = Maze::from(&img);
+	let solution = maze.solve(3, 3).unwrap();
+	
+	assert_eq!(solution.cost, 6);
+	assert_eq!(solution.coords().cloned().collect::<Vec<Coord>>(), vec![
+		Coord::new(0, 0), Coord::new(1, 0), Coord::new(1, 1), Coord::new(1, 2),
+		Coord::new(1, 3), Coord::new(1, 4), Coord::new(0, 4),
+	]);
+	assert_eq!(solution.steps.iter().map(|step| step.dist).collect::<Vec<u32>>(), vec![0, 1, 2, 3, 4, 5, 6]);
+	assert!(solution.pickups.is_empty());
+}
+
+#[test]
+fn test_solution_01_maze20x20() {
+	let img = bmp::open("./inputs/01.maze20x20.bmp").unwrap_or_else(|e| {
+		panic!("Failed to open: {}", e);
+	});
+	
+	let mut maze: Maze = Maze::from(&img);
+	let solution = maze.solve(20, 20).unwrap();
+	
+	assert_eq!(solution.steps.last().unwrap().dist, solution.cost);
+	for (prev, next) in solution.steps.iter().zip(solution.steps.iter().skip(1)) {
+		assert_eq!(prev.coord.row.max(next.coord.row) - prev.coord.row.min(next.coord.row)
+			+ prev.coord.col.max(next.coord.col) - prev.coord.col.min(next.coord.col), 1);
+		assert!(prev.dist < next.dist);
+	}
+	for (color, indx) in &solution.pickups {
+		assert!(!solution.steps[indx - 1].keys.contains(color));
+		assert!(solution.steps[*indx].keys.contains(color));
+	}
+	assert_eq!(solution.pickups.len(), 4);
+}
+
+
+// palette tests
+
+#[test]
+fn test_palette_from_toml() {
+	let palette: Palette = "
+		# shared by the level designers
+		wall = [10, 10, 10]
+		[palette]
+		start = \"#ff00ff\"
+		[tolerance]
+		grey = 2
+	".parse().unwrap();
+	
+	assert_eq!(palette, Palette {
+		wall: Color { r: 10, g: 10, b: 10 },
+		start: Color { r: 255, g: 0, b: 255 },
+		tolerance: Tolerance { grey: 2, color: 0 },
+		..Palette::default()
+	});
+}
+
+#[test]
+fn test_palette_from_json() {
+	let palette: Palette = r##"{ "palette": { "end": [1, 2, 3], "path": "#00ff00" } }"##.parse().unwrap();
+	
+	assert_eq!(palette, Palette {
+		end: Color { r: 1, g: 2, b: 3 },
+		path: Color { r: 0, g: 255, b: 0 },
+		..Palette::default()
+	});
+}
+
+#[test]
+fn test_config_flattened_in_order() {
+	let entries = config::parse("top = 1\n[b]\nx = \"#ff0000\"\n[a.\"c\"]\ny = [1, 2.5, true]\n").unwrap();
+	assert_eq!(entries, vec![
+		("top".to_string(), config::ConfigValue::Number(1.0)),
+		("b.x".to_string(), config::ConfigValue::Text("#ff0000".to_string())),
+		("a.c.y".to_string(), config::ConfigValue::List(vec![
+			config::ConfigValue::Number(1.0), config::ConfigValue::Number(2.5), config::ConfigValue::Bool(true)
+		])),
+	]);
+	assert_eq!(config::parse(r##"{"top": 1, "b": {"x": "#ff0000"}, "a": {"c": {"y": [1, 2.5, true]}}}"##).unwrap(), entries);
+
+	for text in ["when = 1979-05-27", "x = 1\nx = 2", "{\"x\": null}", "{\"x\": 1,}", "[a]\nb = "].iter() {
+		assert_eq!(config::parse(text).err().map(|e| e.kind), Some(MazeErrorKind::Config), "{}", text);
+	}
+}
+
+#[test]
+fn test_palette_invalid() {
+	assert_eq!("walls = [0, 0, 0]".parse::<Palette>().err().map(|e| e.kind), Some(MazeErrorKind::Config));
+	assert_eq!("wall = [0, 0, 256]".parse::<Palette>().err().map(|e| e.kind), Some(MazeErrorKind::Config));
+	assert_eq!("wall = [0, 0".parse::<Palette>().err().map(|e| e.kind), Some(MazeErrorKind::Config));
+}
+
+#[test]
+fn test_find_custom_palette() {
+	let start = Color { r: 10, g: 200, b: 10 };
+	let end = Color { r: 200, g: 10, b: 10 };
+	let wall = Color { r: 255, g: 255, b: 255 };
+	let free = Color { r: 1, g: 1, b: 1 };
+	let img = image_from_rows(&[
+		vec![start, free, wall, end],
+		vec![wall, free, free, free],
+	]);
+	
+	let mut maze: Maze = Maze::from(&img);
+	assert_eq!(maze.find_path(3, 3).err().map(|e| e.kind), Some(MazeErrorKind::NoStart));
+	
+	let mut maze: Maze = Maze::new(&img, Palette { wall, start, end, ..Palette::default() });
+	assert_eq!(maze.solve(3, 3).map(|solution| solution.cost), Ok(5));
+}
+
+
+// tolerance tests
+
+#[test]
+fn test_find_key_color_tolerance() {
+	let free = Color { r: 10, g: 10, b: 10 };
+	let key = Color { r: 200, g: 0, b: 0 };
+	let key_off = Color { r: 201, g: 0, b: 0 };
+	let door = Color { r: 199, g: 1, b: 0 };
+	let img = image_from_rows(&[
+		vec![START_COLOR, free, key, key, key],
+		vec![free, free, key, key, key_off],
+		vec![free, free, key, key, key],
+		vec![door, WALL_COLOR, WALL_COLOR, WALL_COLOR, WALL_COLOR],
+		vec![free, free, free, free, END_COLOR],
+	]);
+	
+	let mut maze: Maze = Maze::from(&img);
+	assert_eq!(maze.find_path(3, 3).err().map(|e| e.kind), Some(MazeErrorKind::MalformedKey { color: key, bbox: (Coord::new(0, 2), Coord::new(2, 4)) }));
+	
+	let palette = Palette { tolerance: Tolerance { grey: 0, color: 2 }, ..Palette::default() };
+	let mut maze: Maze = Maze::new(&img, palette);
+	let solution = maze.solve(3, 3).unwrap();
+	assert_eq!(solution.pickups.iter().map(|(color, _)| *color).collect::<Vec<Color>>(), vec![key]);
+	assert_eq!(solution.cost, 75);
+}
+
+#[test]
+fn test_find_grey_tolerance() {
+	let img = image_from_rows(&[
+		vec![START_COLOR, Color { r: 100, g: 101, b: 100 }, END_COLOR],
+	]);
+	
+	let mut maze: Maze = Maze::from(&img);
+	assert_eq!(maze.find_path(3, 3).err().map(|e| e.kind), Some(MazeErrorKind::Unreachable));
+	
+	let palette = Palette { tolerance: Tolerance { grey: 1, color: 0 }, ..Palette::default() };
+	let mut maze: Maze = Maze::new(&img, palette);
+	assert_eq!(maze.solve(3, 3).map(|solution| solution.cost), Ok(101));
+}
+
+
+#[test]
+fn test_color_tolerance_keeps_grey_floor() {
+	let grey = |level: u8| Color { r: level, g: level, b: level };
+	let img = image_from_rows(&[
+		vec![START_COLOR, grey(127), grey(127), grey(127), END_COLOR],
+	]);
+	let palette = Palette { tolerance: Tolerance { grey: 0, color: 1 }, ..Palette::default() };
+	let mut maze: Maze = Maze::new(&img, palette);
+	assert_eq!(maze.solve(3, 3).map(|solution| solution.cost), Ok(382));
+
+	// подът до началото и края си остава под, а тъмносивото до стената е стена
+	let palette = Palette { tolerance: Tolerance { grey: 0, color: 2 }, ..Palette::default() };
+	for level in [3, 125, 126, 127, 128, 194, 195, 196, 197].iter() {
+		assert_eq!(palette.classify(&grey(*level)), PixelType::FREE, "{}", level);
+	}
+	for level in [0, 1, 2].iter() {
+		assert_eq!(palette.classify(&grey(*level)), PixelType::WALL, "{}", level);
+	}
+	assert_eq!(palette.classify(&Color { r: 1, g: 0, b: 1 }), PixelType::WALL);
+	assert_eq!(palette.classify(&Color { r: 195, g: 196, b: 196 }), PixelType::START);
+	assert_eq!(palette.classify(&Color { r: 126, g: 126, b: 127 }), PixelType::END);
+
+	let mut text: Maze = Maze::parse_text("S33E\n", palette).unwrap();
+	assert_eq!(text.solve(1, 1).map(|solution| solution.cost), Ok(3 + 3 + 1));
+}
+
+#[test]
+fn test_color_tolerance_antialiased_wall() {
+	let grey = |level: u8| Color { r: level, g: level, b: level };
+	let img = image_from_rows(&[
+		vec![START_COLOR, grey(3), END_COLOR],
+		vec![grey(255), grey(255), grey(255)],
+	]);
+	let mut maze: Maze = Maze::new(&img, Palette::default());
+	assert_eq!(maze.solve(1, 1).map(|solution| solution.cost), Ok(3 + 1));
+
+	// ръбът на стената (3, 3, 3) е стена, пътят я заобикаля
+	let palette = Palette { tolerance: Tolerance { grey: 0, color: 3 }, ..Palette::default() };
+	assert_eq!(palette.classify(&grey(3)), PixelType::WALL);
+	let mut maze: Maze = Maze::new(&img, palette);
+	assert_eq!(maze.solve(1, 1).map(|solution| solution.cost), Ok(255 + 255 + 255 + 1));
+}
+
+
+// format tests
+
+#[test]
+fn test_decode_formats_same_cost() {
+	let img = bmp::open("./inputs/01.maze3x3.bmp").unwrap_or_else(|e| {
+		panic!("Failed to open: {}", e);
+	});
+	
+	let mut maze: Maze = Maze::from(&img);
+	let cost = maze.solve(3, 3).unwrap().cost;
+	
+	let raw = RawImage::from(&img);
+	for format in [ImageFormat::Bmp, ImageFormat::Png, ImageFormat::Ppm, ImageFormat::Gif].iter() {
+		let bytes = format::encode(&raw, *format).unwrap();
+		
+		let mut maze: Maze = Maze::decode(&bytes, Palette::default()).unwrap();
+		assert_eq!(maze.format(), *format);
+		assert_eq!(maze.solve(3, 3).unwrap().cost, cost, "{:?}", format);
+	}
+	
+	// PGM държи само сиви пиксели - ключовете не могат да се запишат
+	assert_eq!(format::encode(&raw, ImageFormat::Pgm).unwrap_err().kind, MazeErrorKind::Decode);
+	
+	// сив лабиринт с начало и край в сиви цветове от палитрата
+	let palette = Palette { start: Color { r: 200, g: 200, b: 200 }, end: Color { r: 100, g: 100, b: 100 }, ..Palette::default() };
+	let grey = |v: u8| Color { r: v, g: v, b: v };
+	let raw = RawImage { width: 4, height: 2, colors: vec![grey(200), grey(255), grey(0), grey(255), grey(255), grey(30), grey(255), grey(100)] };
+	let cost = Maze::decode(&format::encode(&raw, ImageFormat::Ppm).unwrap(), palette).unwrap().solve(1, 1).unwrap().cost;
+	let bytes = format::encode(&raw, ImageFormat::Pgm).unwrap();
+	assert!(bytes.starts_with(b"P5"));
+	let mut maze: Maze = Maze::decode(&bytes, palette).unwrap();
+	assert_eq!(maze.format(), ImageFormat::Pgm);
+	assert_eq!(maze.solve(1, 1).unwrap().cost, cost);
+}
+
+#[test]
+fn test_decode_ascii_pnm() {
+	let ppm = b"P3\n# start, grey 255 and end\n3 1\n255\n195 195 196  255 255 255  126 127 127\n";
+	let mut maze: Maze = Maze::decode(ppm, Palette::default()).unwrap();
+	assert_eq!(maze.format(), ImageFormat::Ppm);
+	assert_eq!(maze.solve(3, 3).map(|solution| solution.cost), Ok(256));
+	
+	let pgm = b"P2 2 2 15 15 0 7 15";
+	let maze: Maze = Maze::decode(pgm, Palette::default()).unwrap();
+	assert_eq!(maze.format(), ImageFormat::Pgm);
+	assert_eq!(maze.colors, vec![
+		Color { r: 255, g: 255, b: 255 }, Color { r: 0, g: 0, b: 0 },
+		Color { r: 119, g: 119, b: 119 }, Color { r: 255, g: 255, b: 255 },
+	]);
+}
+
+#[test]
+fn test_decode_errors() {
+	assert_eq!(Maze::decode(b"\x00 not an image", Palette::default()).err().map(|e| e.kind), Some(MazeErrorKind::Decode));
+	assert_eq!(Maze::decode(b"P6 10 10 255\n\x00\x00", Palette::default()).err().map(|e| e.kind), Some(MazeErrorKind::Decode));
+	assert_eq!(Maze::decode(b"\x89PNG\r\n\x1a\nbroken", Palette::default()).err().map(|e| e.kind), Some(MazeErrorKind::Decode));
+}
+
+#[test]
+fn test_decode_huge_header() {
+	let mut bmp = b"BM".to_vec();
+	bmp.resize(30, 0);
+	bmp[18..22].copy_from_slice(&70000i32.to_le_bytes());
+	bmp[22..26].copy_from_slice(&(-70000i32).to_le_bytes());
+	let mut gif = b"GIF89a".to_vec();
+	gif.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0x2c, 0, 0, 0, 0, 1, 0, 1, 0, 0, 2, 2, 0x44, 1, 0, 0x3b]);
+
+	// заглавията обещават милиарди пиксели в няколко байта
+	let files: [&[u8]; 5] = [b"P3 100000 100000 255\n", b"P6 70000 70000 255\n\x00", b"P5 4294967295 4294967295 65535\n", &bmp, &gif];
+	for bytes in files.iter() {
+		assert_eq!(Maze::decode(bytes, Palette::default()).err().map(|e| e.kind), Some(MazeErrorKind::Decode), "{:?}", &bytes[..6]);
+	}
+	assert!(matches!(Maze::open("./inputs/missing.png").err().map(|e| e.kind), Some(MazeErrorKind::Io(_))));
+}
+
+
+// text tests
+
+#[test]
+fn test_found_01_maze_txt() {
+	let mut maze: Maze = Maze::open("./inputs/01.maze.txt").unwrap_or_else(|e| {
+		panic!("Failed to open: {}", e);
+	});
+	assert_eq!(maze.format(), ImageFormat::Text);
+	
+	let solution = maze.solve(20, 20).unwrap();
+	assert_eq!(solution.cost, 17);
+	assert_eq!(solution.pickups, vec![(text::letter_color('a'), 12)]);
+}
+
+#[test]
+fn test_render_01_maze_txt() {
+	let text = std::fs::read_to_string("./inputs/01.maze.txt").unwrap();
+	let mut maze: Maze = Maze::parse_text(&text, Palette::default()).unwrap();
+	assert_eq!(maze.render_text().unwrap(), text);
+	
+	let _ = maze.find_path(1, 1);
+	assert_eq!(maze.render_text().unwrap(), "\
+##########
+#S..#....#
+#*#.#.##.#
+#*#...#a.#
+#*#####*##
+#*******A#
+########E#
+##########
+");
+}
+
+#[test]
+fn test_text_door_without_key() {
+	let mut maze: Maze = Maze::parse_text("SaA.E", Palette::default()).unwrap();
+	assert_eq!(maze.solve(1, 1).map(|solution| solution.cost), Ok(4));
+	
+	let mut maze: Maze = Maze::parse_text("SbA.E", Palette::default()).unwrap();
+	assert_eq!(maze.find_path(1, 1).err().map(|e| e.kind), Some(MazeErrorKind::Unreachable));
+	
+	assert_eq!(Maze::parse_text("S.?.E", Palette::default()).err().map(|e| e.kind), Some(MazeErrorKind::Decode));
+}
+
+#[test]
+fn test_save_01_maze_txt() {
+	let mut maze: Maze = Maze::open("./inputs/01.maze.txt").unwrap_or_else(|e| {
+		panic!("Failed to open: {}", e);
+	});
+	let _ = maze.find_path(1, 1);
+	assert_eq!(maze.save_path("./outputs/01.maze.txt").err(), Option::<MazeError>::None);
+}
+
+
+// cli support tests
+
+#[test]
+fn test_color_from_str() {
+	let color = Color { r: 255, g: 201, b: 14 };
+	assert_eq!("#ffc90e".parse::<Color>(), Ok(color));
+	assert_eq!("255,201,14".parse::<Color>(), Ok(color));
+	assert_eq!(color.to_string().parse::<Color>(), Ok(color));
+	assert_eq!("#ffc9".parse::<Color>().err().map(|e| e.kind), Some(MazeErrorKind::Config));
+}
+
+#[test]
+fn test_info_01_maze_txt() {
+	let maze: Maze = Maze::open("./inputs/01.maze.txt").unwrap();
+	let info = maze.info();
+	assert_eq!((info.width, info.height, info.format), (10, 8, ImageFormat::Text));
+	assert_eq!((info.walls, info.free, info.start, info.end, info.colored), (52, 24, 1, 1, 2));
+	assert_eq!(info.colors, vec![text::letter_color('a')]);
+}
+
+
+// batch tests
+
+#[test]
+fn test_batch_3x3() {
+	let batch = Batch {
+		key_size: KeySize::Fixed { height: 3, width: 3 },
+		output_dir: Some(std::path::PathBuf::from("./outputs/batch")),
+		jobs: 2,
+		..Default::default()
+	};
+	let entries = batch.run("./inputs/0[1-4].maze3x3.bmp").unwrap();
+	
+	let statuses: Vec<BatchStatus> = entries.iter().map(|entry| entry.status).collect();
+	assert_eq!(statuses, vec![BatchStatus::Solved, BatchStatus::Solved, BatchStatus::NoEnd, BatchStatus::NoStart]);
+	assert_eq!((entries[0].cost, entries[0].keys), (Some(33196), 2));
+	assert!(entries[0].explored > 0);
+	assert_eq!(entries[0].output, Some(std::path::PathBuf::from("./outputs/batch/01.maze3x3.bmp")));
+	assert!(std::path::Path::new("./outputs/batch/01.maze3x3.bmp").is_file());
+	assert_eq!(entries[2].output, None);
+	
+	let csv = report_csv(&entries);
+	assert_eq!(csv.lines().count(), 5);
+	assert!(csv.lines().nth(4).unwrap().starts_with("inputs/04.maze3x3.bmp,NoStart,,0,0,0,"));
+	assert!(report_json(&entries).contains("\"status\": \"NoEnd\""));
+}
+
+#[test]
+fn test_batch_list_inputs() {
+	let (base, inputs) = list_inputs("./inputs").unwrap();
+	assert_eq!(base, std::path::PathBuf::from("./inputs"));
+	assert_eq!(inputs.len(), 12);
+	
+	assert!(matches!(list_inputs("./inputs/*.png").err().map(|e| e.kind), Some(MazeErrorKind::Io(_))));
+}
+
+
+// error tests
+
+#[test]
+fn test_multiple_starts() {
+	let img = image_from_rows(&[
+		vec![START_COLOR, WALL_COLOR, START_COLOR],
+		vec![Color { r: 1, g: 1, b: 1 }, WALL_COLOR, END_COLOR],
+	]);
+	
+	// само втората начална област стига до края
+	let mut maze: Maze = Maze::from(&img);
+	maze.find_path(3, 3).unwrap();
+	let solution = maze.solution().unwrap();
+	assert_eq!((solution.start, solution.cost), (1, 1));
+	assert_eq!(solution.steps[0].coord, Coord::new(0, 2));
+	// the second start is a lint, not an error
+	assert!(maze.validate(3, 3).iter().any(|lint| lint.kind == LintKind::MultipleStarts));
+
+	let results = maze.solve_each_start(KeySize::Fixed { height: 3, width: 3 }, false).unwrap();
+	assert_eq!(results.len(), 2);
+	assert_eq!(results[0].as_ref().err().map(|e| &e.kind), Some(&MazeErrorKind::Unreachable));
+	assert_eq!(results[1].as_ref().map(|solution| solution.start).ok(), Some(1));
+
+	let e = maze.solve_single_start(KeySize::Fixed { height: 3, width: 3 }, false).err().unwrap();
+	assert_eq!((e.kind, e.coord), (MazeErrorKind::MultipleStarts, Some(Coord::new(0, 2))));
+	let mut maze: Maze = Maze::parse_text("S..E", Palette::default()).unwrap();
+	assert_eq!(maze.solve_single_start(KeySize::Any, true).map(|solution| solution.cost), Ok(3));
+}
+
+#[test]
+fn test_each_start_text() {
+	let mut maze: Maze = Maze::parse_text("S....E..S", Palette::default()).unwrap();
+	maze.find_path(1, 1).unwrap();
+	assert_eq!(maze.solution().unwrap().cost, 3);
+
+	let results = maze.solve_each_start(KeySize::Any, true).unwrap();
+	let costs: Vec<(usize, u32)> = results.into_iter().map(|result| result.map(|solution| (solution.start, solution.cost)).unwrap()).collect();
+	assert_eq!(costs, vec![(0, 5), (1, 3)]);
+	assert_eq!(Maze::parse_text("#E#", Palette::default()).unwrap().solve_each_start(KeySize::Any, false).err().map(|e| e.kind), Some(MazeErrorKind::NoStart));
+}
+
+#[test]
+fn test_error_io_source() {
+	use std::error::Error;
+	
+	let e = Maze::open("./inputs/missing.png").err().unwrap();
+	let source = e.source().and_then(|source| source.downcast_ref::<std::io::Error>());
+	assert_eq!(source.map(|source| source.kind()), Some(std::io::ErrorKind::NotFound));
+	assert!(e.to_string().starts_with("I/O error: Failed to read ./inputs/missing.png: "));
+	
+	for e in [Palette::load("./inputs/missing.toml").err(), Schedule::load("./inputs/missing.toml").err(), TableCost::load("./inputs/missing.toml").err()].iter() {
+		let e = e.as_ref().unwrap();
+		assert!(matches!(e.kind, MazeErrorKind::Io(_)), "{}", e);
+		assert!(e.source().is_some());
+		assert!(e.to_string().starts_with("I/O error: Failed to read ./inputs/missing.toml: "));
+	}
+	
+	let e = Maze::decode(b"\x89PNG\r\n\x1a\nbroken", Palette::default()).err().unwrap();
+	assert!(e.source().is_some());
+	
+	let mut maze: Maze = Maze::open("./inputs/01.maze.txt").unwrap();
+	maze.find_path(1, 1).unwrap();
+	let e = maze.save_path("./outputs/missing/01.maze.png").unwrap_err();
+	assert!(matches!(e.kind, MazeErrorKind::Io(_)));
+}
+
+
+// diagnosis tests
+
+#[test]
+fn test_diagnose_reasons() {
+	let diagnose = |text: &str| {
+		let mut maze: Maze = Maze::parse_text(text, Palette::default()).unwrap();
+		let _ = maze.find_path(1, 1);
+		maze.diagnose().unwrap()
+	};
+	
+	let diagnosis = diagnose("S.#.E");
+	assert_eq!(diagnosis.reason, NoPathReason::WalledOff);
+	assert_eq!(diagnosis.reachable, (Coord::new(0, 0), Coord::new(0, 1)));
+	assert_eq!(diagnosis.explored, vec![true, true, false, false, false]);
+	
+	let diagnosis = diagnose("S.A.E");
+	assert_eq!(diagnosis.reason, NoPathReason::DoorWithoutKey);
+	assert_eq!(diagnosis.blocking_doors, vec![text::letter_color('a')]);
+	assert_eq!(diagnosis.doors_without_key, vec![text::letter_color('a')]);
+	
+	let diagnosis = diagnose("S.A.E#a");
+	assert_eq!(diagnosis.reason, NoPathReason::KeyOutOfReach);
+	assert_eq!(diagnosis.doors_without_key, vec![]);
+	
+	assert_eq!(diagnose("S.A.#").reason, NoPathReason::NoEnd);
+	
+	// вратата е отворена с ключа, краят е зад стена
+	let diagnosis = diagnose("aSA.#E");
+	assert_eq!(diagnosis.reason, NoPathReason::WalledOff);
+	assert_eq!(diagnosis.blocking_doors, vec![]);
+	
+	let mut maze: Maze = Maze::parse_text("SaA.E", Palette::default()).unwrap();
+	maze.find_path(1, 1).unwrap();
+	assert_eq!(maze.diagnose(), None);
+}
+
+#[test]
+fn test_diagnose_02_maze20x20() {
+	let mut maze: Maze = Maze::open("./inputs/02.maze20x20.bmp").unwrap();
+	let e = maze.find_path(20, 20).err().unwrap();
+	assert_eq!(e.kind, MazeErrorKind::Unreachable);
+	
+	let diagnosis = maze.diagnose().unwrap();
+	assert_eq!(diagnosis.reason, NoPathReason::DoorWithoutKey);
+	assert_eq!(diagnosis.doors_without_key, vec![Color { r: 0x22, g: 0xb1, b: 0x4c }]);
+	assert_eq!(maze.save_diagnosis(&diagnosis, "./outputs/02.maze20x20.diagnosis.png").err(), Option::<MazeError>::None);
+}
+
+
+// lint tests
+
+#[test]
+fn test_validate_lints() {
+	let f = Color { r: 1, g: 1, b: 1 };
+	let g = Color { r: 34, g: 177, b: 76 };
+	let b = Color { r: 0, g: 162, b: 232 };
+	let y = Color { r: 255, g: 201, b: 14 };
+	let z = Color { r: 0, g: 0, b: 0 };
+	let n = Color { r: 126, g: 127, b: 129 };
+	let s = START_COLOR;
+	let img = image_from_rows(&[
+		vec![s, f, g, g, g, f, g, g, g],
+		vec![f, f, g, g, g, f, g, g, g],
+		vec![f, f, g, g, g, f, f, f, f],
+		vec![b, b, b, f, y, f, g, g, g],
+		vec![b, b, b, f, f, f, g, g, g],
+		vec![f, f, f, f, f, f, g, g, g],
+		vec![f, n, f, f, f, f, f, f, END_COLOR],
+		vec![s, f, z, f, f, f, f, f, f],
+	]);
+	
+	let palette = Palette { wall: Color { r: 1, g: 2, b: 3 }, ..Palette::default() };
+	let mut maze: Maze = Maze::new(&img, palette);
+	let lints = maze.validate(3, 3);
+	
+	let kinds: Vec<LintKind> = lints.iter().map(|lint| lint.kind).collect();
+	assert_eq!(kinds, vec![
+		LintKind::NearMissKey { width: 3, height: 2 },
+		LintKind::DuplicateKey,
+		LintKind::DoorWithoutKey,
+		// the colour close to the end is drawn as a door too
+		LintKind::DoorWithoutKey,
+		LintKind::MultipleStarts,
+		LintKind::ZeroWeight { count: 1 },
+		LintKind::NearReservedColor { reserved: END_COLOR },
+	]);
+	assert_eq!(lints[0].bbox, (Coord::new(3, 0), Coord::new(4, 2)));
+	assert_eq!(lints[1].bbox, (Coord::new(3, 6), Coord::new(5, 8)));
+	assert_eq!(lints[2].color, y);
+	assert_eq!(lints[3].color, n);
+	assert_eq!(lints[4].bbox, (Coord::new(7, 0), Coord::new(7, 0)));
+	assert_eq!(lints[2].to_string(), "There is no key for the #ffc90e door at (3, 4) - (3, 4)");
+}
+
+#[test]
+fn test_validate_grey_floor_near_reserved() {
+	let grey = |level: u8| Color { r: level, g: level, b: level };
+	let img = image_from_rows(&[
+		vec![START_COLOR, grey(190), grey(196), grey(130), grey(126), END_COLOR],
+	]);
+	let mut maze: Maze = Maze::from(&img);
+	assert_eq!(maze.validate(3, 3), Vec::<Lint>::new());
+	assert_eq!(maze.solve(3, 3).map(|solution| solution.cost), Ok(643));
+}
+
+#[test]
+fn test_validate_key_without_door() {
+	let mut maze: Maze = Maze::parse_text("Sa.bB.E", Palette::default()).unwrap();
+	let lints = maze.validate(1, 1);
+	assert_eq!(lints.len(), 1);
+	assert_eq!((lints[0].kind, lints[0].color), (LintKind::KeyWithoutDoor, text::letter_color('a')));
+	
+	let mut maze: Maze = Maze::open("./inputs/01.maze.txt").unwrap();
+	assert_eq!(maze.validate(1, 1), vec![]);
+}
+
+
+// key size tests
+
+#[test]
+fn test_detect_key_size() {
+	let mut maze: Maze = Maze::open("./inputs/01.maze3x3.bmp").unwrap();
+	assert_eq!(maze.detect_key_size(), Ok(Some((3, 3))));
+	
+	let mut maze: Maze = Maze::open("./inputs/01.maze20x20.bmp").unwrap();
+	assert_eq!(maze.detect_key_size(), Ok(Some((20, 20))));
+	maze.find_path_with(KeySize::Auto, false).unwrap();
+	// every START pixel is a source, so the path leaves from the one closest to the end
+	assert_eq!(maze.solution().unwrap().cost, 584464);
+	
+	let mut maze: Maze = Maze::open("./inputs/01.maze.txt").unwrap();
+	assert_eq!(maze.detect_key_size(), Ok(None));
+}
+
+// a red 2x2 key and a green 3x3 key, each with a door in the single corridor
+fn mixed_key_sizes() -> bmp::Image {
+	let f = Color { r: 1, g: 1, b: 1 };
+	let w = WALL_COLOR;
+	let r = Color { r: 237, g: 28, b: 36 };
+	let g = Color { r: 34, g: 177, b: 76 };
+	image_from_rows(&[
+		vec![START_COLOR, f, r, r, f, g, g, g],
+		vec![f, f, r, r, f, g, g, g],
+		vec![f, f, f, f, f, g, g, g],
+		vec![w, w, w, w, w, w, w, r],
+		vec![w, w, w, w, w, w, w, g],
+		vec![w, w, w, w, w, w, w, END_COLOR],
+	])
+}
+
+#[test]
+fn test_detect_key_size_ambiguous() {
+	let mut maze: Maze = Maze::from(&mixed_key_sizes());
+	assert_eq!(maze.key_size_candidates(), vec![
+		KeySizeCandidate { height: 2, width: 2, count: 1 },
+		KeySizeCandidate { height: 3, width: 3, count: 1 },
+	]);
+	assert_eq!(maze.detect_key_size().err().map(|e| e.kind), Some(MazeErrorKind::AmbiguousKeySize));
+	assert_eq!(maze.find_path_with(KeySize::Auto, false).err().map(|e| e.kind), Some(MazeErrorKind::AmbiguousKeySize));
+}
+
+#[test]
+fn test_find_path_any_key_size() {
+	let mut maze: Maze = Maze::from(&mixed_key_sizes());
+	assert_eq!(maze.find_path(2, 2).err().map(|e| e.kind), Some(MazeErrorKind::Unreachable));
+	
+	maze.find_path_with(KeySize::Any, false).unwrap();
+	let solution = maze.solution().unwrap();
+	assert_eq!(solution.pickups.len(), 2);
+	assert_eq!(solution.steps.last().unwrap().coord, Coord::new(5, 7));
+}
+
+#[test]
+fn test_key_size_from_str() {
+	assert_eq!("3x4".parse::<KeySize>(), Ok(KeySize::Fixed { height: 4, width: 3 }));
+	assert_eq!("Auto".parse::<KeySize>(), Ok(KeySize::Auto));
+	assert_eq!("any".parse::<KeySize>(), Ok(KeySize::Any));
+	assert_eq!(KeySize::Fixed { height: 4, width: 3 }.to_string(), "3x4");
+	assert_eq!("0x3".parse::<KeySize>().err().map(|e| e.kind), Some(MazeErrorKind::Config));
+}
+
+// key combination tests
+
+#[test]
+fn test_key_comb_canonical() {
+	use std::collections::HashSet;
+	use key_comb::KeyComb;
+
+	let forward = (0..200).fold(KeyComb::default(), |comb, pos| comb.with(pos));
+	let backward = (0..200).rev().fold(KeyComb::default(), |comb, pos| comb.with(pos));
+	assert_eq!(forward, backward);
+	assert_eq!(forward.positions().count(), 200);
+	assert!(forward.has(199) && !forward.has(200) && !forward.has(1000));
+
+	// 127 is the last key that fits in the small mask, 128 is the first that doesn't
+	assert_eq!(KeyComb::default().with(127), KeyComb::Small(1 << 127));
+	assert!(matches!(KeyComb::default().with(128), KeyComb::Wide(_)));
+	assert_eq!(KeyComb::default().with(3).with(3), KeyComb::default().with(3));
+
+	let combs: HashSet<KeyComb> = vec![
+		KeyComb::default().with(1).with(130),
+		KeyComb::default().with(130).with(1),
+		KeyComb::default().with(1),
+	].into_iter().collect();
+	assert_eq!(combs.len(), 2);
+}
+
+// a dead end corridor of 140 one pixel keys above a corridor of their doors,
+// the end is only reached with every key
+#[test]
+fn test_find_path_140_keys() {
+	const KEYS: usize = 140;
+	let key = |i: usize| Color { r: 200, g: i as u8, b: 50 };
+	let f = Color { r: 1, g: 1, b: 1 };
+	let mut keys: Vec<Color> = (0..KEYS).map(key).collect();
+	keys.push(f);
+	let doors: Vec<Color> = keys.clone();
+	let mut wall: Vec<Color> = vec![WALL_COLOR; KEYS + 1];
+	wall[KEYS] = f;
+	let mut bottom: Vec<Color> = vec![WALL_COLOR; KEYS + 1];
+	bottom[0] = END_COLOR;
+	bottom[KEYS] = START_COLOR;
+
+	let mut maze: Maze = Maze::from(&image_from_rows(&[keys, wall, doors.clone(), doors, bottom]));
+	maze.find_path(1, 1).unwrap();
+	let solution = maze.solution().unwrap();
+	assert_eq!(solution.pickups.len(), KEYS);
+	assert_eq!(solution.steps.last().unwrap().keys.len(), KEYS);
+	assert_eq!(solution.steps.last().unwrap().coord, Coord::new(4, 0));
+}
+
+// consumable keys tests
+
+fn consumable(text: &str) -> Maze {
+	let mut maze: Maze = Maze::parse_text(text, Palette::default()).unwrap();
+	maze.set_key_rules(KeyRules::Consumable);
+	maze
+}
+
+#[test]
+fn test_consumable_keys_spent() {
+	let mut maze: Maze = Maze::parse_text("SaA.AE", Palette::default()).unwrap();
+	maze.find_path(1, 1).unwrap();
+	assert!(maze.solution().unwrap().openings.is_empty());
+
+	// един ключ стига само за първата от двете врати
+	let mut maze: Maze = consumable("SaA.AE");
+	assert_eq!(maze.find_path(1, 1).err().map(|e| e.kind), Some(MazeErrorKind::Unreachable));
+
+	let mut maze: Maze = consumable("Sa.aA.AE");
+	maze.find_path(1, 1).unwrap();
+	let solution = maze.solution().unwrap();
+	let a = text::letter_color('a');
+	assert_eq!(solution.pickups, vec![(a, 1), (a, 3)]);
+	assert_eq!(solution.openings, vec![(a, 4), (a, 6)]);
+	assert_eq!(solution.steps[3].keys, vec![a, a]);
+	assert_eq!(solution.steps[5].keys, vec![a]);
+	assert!(solution.steps[7].keys.is_empty());
+}
+
+#[test]
+fn test_consumable_keys_door_stays_open() {
+	// a wide key area is one key and a wide door area is opened once
+	let mut maze: Maze = consumable("SaaAAAE");
+	maze.find_path(1, 1).unwrap();
+	assert_eq!(maze.solution().unwrap().openings.len(), 1);
+
+	// the key is behind the door, which stays open on the way back
+	let mut maze: Maze = consumable("bA.SaB.E");
+	maze.find_path(1, 1).unwrap();
+	let solution = maze.solution().unwrap();
+	let colors: Vec<Color> = solution.openings.iter().map(|(color, _)| *color).collect();
+	assert_eq!(colors, vec![text::letter_color('a'), text::letter_color('b')]);
+}
+
+// a 2x2 key, a 7x7 door of its colour and the end, the door gets the number 1 drawn in it
+#[test]
+fn test_consumable_keys_save_path() {
+	let f = Color { r: 1, g: 1, b: 1 };
+	let r = Color { r: 237, g: 28, b: 36 };
+	let rows: Vec<Vec<Color>> = (0..7).map(|row| {
+		let key = if row < 2 { r } else { f };
+		let mut colors = vec![START_COLOR, key, key, f];
+		colors.extend(vec![r; 7]);
+		colors.push(END_COLOR);
+		colors
+	}).collect();
+
+	let mut maze: Maze = Maze::from(&image_from_rows(&rows));
+	maze.set_key_rules(KeyRules::Consumable);
+	maze.find_path(2, 2).unwrap();
+	assert_eq!(maze.solution().unwrap().openings.len(), 1);
+	assert_eq!(maze.save_path("./outputs/consumable.bmp").err(), Option::<MazeError>::None);
+
+	// "1" is 3x5 pixels in the middle of the door, its top row is only the middle pixel
+	let img = bmp::open("./outputs/consumable.bmp").unwrap();
+	assert_eq!(Color::from(img.get_pixel(7, 1)), WALL_COLOR);
+	assert_ne!(Color::from(img.get_pixel(6, 1)), WALL_COLOR);
+	assert_eq!(Color::from(img.get_pixel(8, 5)), WALL_COLOR);
+}
+
+// the same door with its top left corner cut out, the number is only drawn over the door
+#[test]
+fn test_consumable_keys_save_path_l_door() {
+	let f = Color { r: 1, g: 1, b: 1 };
+	let r = Color { r: 237, g: 28, b: 36 };
+	let rows: Vec<Vec<Color>> = (0..7).map(|row| {
+		let key = if row < 2 { r } else { f };
+		let mut colors = vec![START_COLOR, key, key, f];
+		colors.extend((4..11).map(|col| if row < 3 && col < 8 { f } else { r }));
+		colors.push(END_COLOR);
+		colors
+	}).collect();
+
+	let mut maze: Maze = Maze::from(&image_from_rows(&rows));
+	maze.set_key_rules(KeyRules::Consumable);
+	maze.find_path(2, 2).unwrap();
+	assert_eq!(maze.solution().unwrap().openings.len(), 1);
+	assert_eq!(maze.save_path("./outputs/consumable_l.bmp").err(), Option::<MazeError>::None);
+
+	let img = bmp::open("./outputs/consumable_l.bmp").unwrap();
+	for (x, y) in [(7, 1), (6, 2), (7, 2)].iter() {
+		assert_ne!(Color::from(img.get_pixel(*x, *y)), WALL_COLOR, "{} {}", x, y);
+	}
+	assert_eq!(Color::from(img.get_pixel(8, 5)), WALL_COLOR);
+	assert_eq!(Color::from(img.get_pixel(7, 3)), WALL_COLOR);
+}
+
+// checkpoint tests
+
+fn text_maze(text: &str) -> Maze {
+	Maze::parse_text(text, Palette::default()).unwrap()
+}
+
+#[test]
+fn test_waypoint_detour() {
+	let mut maze: Maze = text_maze("\
+		S.E\n\
+		.#.\n\
+		...\n");
+	maze.set_waypoints(vec![Coord::new(2, 1)]);
+	maze.find_path(1, 1).unwrap();
+	let solution = maze.solution().unwrap();
+	assert_eq!(solution.cost, 6);
+	assert_eq!(solution.checkpoints, vec![(0, 3)]);
+	assert_eq!(solution.steps[3].coord, Coord::new(2, 1));
+}
+
+#[test]
+fn test_waypoint_on_start() {
+	let mut maze: Maze = text_maze("\
+		S.E\n\
+		.#.\n\
+		...\n");
+	maze.set_waypoints(vec![Coord::new(0, 0)]);
+	let solution = maze.solve(1, 1).unwrap();
+	assert_eq!(solution.cost, 2);
+	assert_eq!(solution.checkpoints, vec![(0, 0)]);
+
+	// с други две точки редът е фиксиран отначало и стартът е първата по него
+	let mut maze: Maze = text_maze("S.........E");
+	let waypoints: Vec<Coord> = (0..=EXACT_CHECKPOINTS as u32).map(|col| Coord::new(0, col)).collect();
+	maze.set_waypoints(waypoints);
+	maze.find_path_astar(1, 1).unwrap();
+	assert_eq!(maze.solution().unwrap().cost, 10);
+}
+
+#[test]
+fn test_waypoints_best_order() {
+	// първо се минава през по-близката точка вляво, независимо от реда, в който са дадени
+	let mut maze: Maze = text_maze("..S...E");
+	maze.set_waypoints(vec![Coord::new(0, 4), Coord::new(0, 0)]);
+	maze.find_path(1, 1).unwrap();
+	let solution = maze.solution().unwrap();
+	assert_eq!(solution.cost, 8);
+	assert_eq!(solution.checkpoints, vec![(1, 2), (0, 6)]);
+
+	let mut maze: Maze = text_maze("..S...E");
+	maze.set_waypoints(vec![Coord::new(0, 1)]);
+	maze.find_path_astar(1, 1).unwrap();
+	assert_eq!(maze.solution().unwrap().cost, 6);
+}
+
+#[test]
+fn test_waypoints_nearest_neighbour() {
+	// more than EXACT_CHECKPOINTS waypoints are visited nearest first
+	let mut maze: Maze = text_maze("S..................E");
+	maze.set_waypoints((1..=9).rev().map(|col| Coord::new(0, col * 2)).collect());
+	maze.find_path(1, 1).unwrap();
+	let solution = maze.solution().unwrap();
+	assert_eq!(solution.cost, 19);
+	let visited: Vec<usize> = solution.checkpoints.iter().map(|(checkpoint, _)| *checkpoint).collect();
+	assert_eq!(visited, (0..9).rev().collect::<Vec<usize>>());
+}
+
+#[test]
+fn test_checkpoint_color() {
+	let palette = Palette { checkpoint: Some(text::letter_color('c')), ..Palette::default() };
+	let mut maze: Maze = Maze::parse_text("E.S.CC", palette).unwrap();
+	maze.find_path(1, 1).unwrap();
+	let solution = maze.solution().unwrap();
+	assert_eq!(solution.cost, 6);
+	assert_eq!(solution.checkpoints, vec![(0, 2)]);
+
+	let mut maze: Maze = Maze::parse_text("S.E#C", palette).unwrap();
+	let e = maze.find_path(1, 1).err().unwrap();
+	assert_eq!(e.kind, MazeErrorKind::Unreachable);
+	assert_eq!(e.coord, Some(Coord::new(0, 4)));
+
+	let mut maze: Maze = text_maze("S.E#.");
+	maze.set_waypoints(vec![Coord::new(0, 3)]);
+	assert_eq!(maze.find_path(1, 1).err().map(|e| e.kind), Some(MazeErrorKind::Config));
+}
+
+// connectivity tests
+
+#[test]
+fn test_diagonal_moves() {
+	let open_field = "S....\n.....\n.....\n.....\n....E\n";
+	let mut maze: Maze = text_maze(open_field);
+	maze.find_path(1, 1).unwrap();
+	assert_eq!(maze.solution().unwrap().cost, 8);
+
+	maze.set_connectivity(Connectivity::Eight);
+	maze.find_path(1, 1).unwrap();
+	let solution = maze.solution().unwrap();
+	assert_eq!(solution.cost, 4 * 141);
+	assert_eq!(solution.len(), 5);
+	maze.find_path_astar(1, 1).unwrap();
+	assert_eq!(maze.solution().unwrap().cost, 4 * 141);
+}
+
+#[test]
+fn test_diagonal_corner_cutting() {
+	let costs = |text: &str| -> Vec<Option<u32>> {
+		[Connectivity::Four, Connectivity::Eight, Connectivity::EightCutCorners].iter().map(|connectivity| {
+			let mut maze: Maze = text_maze(text);
+			maze.set_connectivity(*connectivity);
+			maze.solve(1, 1).ok().map(|solution| solution.cost)
+		}).collect()
+	};
+
+	assert_eq!(costs("S#\n#E\n"), vec![None, None, Some(141)]);
+	assert_eq!(costs("S#\n.E\n"), vec![Some(2), Some(200), Some(141)]);
+	// cutting the corner of a door doesn't need its key
+	assert_eq!(costs("Sa.\n#.A\n##E\n"), vec![Some(4), Some(341), Some(282)]);
+	// but a diagonal step into a door does
+	assert_eq!(costs("Sa..\n....\nAAAA\n...E\n"), vec![Some(6), Some(482), Some(482)]);
+	assert_eq!(costs("S...\n....\nAAAA\n...E\n"), vec![None, None, None]);
+}
+
+#[test]
+fn test_diagonal_moves_astar() {
+	let mut maze: Maze = Maze::open("./inputs/01.maze20x20.bmp").unwrap();
+	maze.set_connectivity(Connectivity::EightCutCorners);
+	maze.find_path(20, 20).unwrap();
+	let cost = maze.solution().unwrap().cost;
+	maze.find_path_astar(20, 20).unwrap();
+	assert_eq!(maze.solution().unwrap().cost, cost);
+	assert_eq!(maze.save_path("./outputs/01.maze20x20.diagonal.bmp").err(), Option::<MazeError>::None);
+}
+
+// topology tests
+
+// draws a text maze with every cell laid out by the topology, on a wall background
+fn topology_image(text: &str, topology: Topology) -> bmp::Image {
+	let cells: RawImage = Maze::parse_text(text, Palette::default()).unwrap().images.remove(0);
+	let (width, height) = match topology.shape {
+		CellShape::Square => (cells.width * topology.cell_width, cells.height * topology.cell_height),
+		CellShape::Hex => (cells.width * topology.cell_width + topology.cell_width / 2, cells.height * topology.cell_height),
+		CellShape::Triangle => ((cells.width + 1) * topology.cell_width / 2, cells.height * topology.cell_height),
+	};
+
+	let mut img = bmp::Image::new(width, height);
+	for y in 0..height {
+		for x in 0..width {
+			img.set_pixel(x, y, WALL_COLOR.into());
+		}
+	}
+	for row in 0..cells.height {
+		for col in 0..cells.width {
+			let color = cells.colors[(row * cells.width + col) as usize];
+			for (x, y) in topology.pixels(&Coord::new(row, col), width, height) {
+				img.set_pixel(x, y, color.into());
+			}
+		}
+	}
+	img
+}
+
+fn topology_cost(text: &str, topology: &str) -> Option<u32> {
+	let mut maze: Maze = Maze::from(&topology_image(text, topology.parse().unwrap()));
+	maze.set_topology(topology.parse().unwrap()).unwrap();
+	maze.solve(1, 1).ok().map(|solution| solution.cost)
+}
+
+#[test]
+fn test_topology_parse() {
+	assert_eq!("hex:12x10".parse::<Topology>().unwrap(), Topology { shape: CellShape::Hex, cell_width: 12, cell_height: 10 });
+	assert_eq!("Triangle".parse::<Topology>().unwrap().to_string(), "triangle:1x1");
+	assert_eq!("square:3x3".parse::<Topology>().unwrap().to_string(), "square:3x3");
+	for text in ["circle", "hex:12", "hex:0x3", "square:ax3"].iter() {
+		assert_eq!(text.parse::<Topology>().err().map(|e| e.kind), Some(MazeErrorKind::Config), "{}", text);
+	}
+}
+
+#[test]
+fn test_square_cells() {
+	let text = "S..#\n.#..\n#..E\n";
+	assert_eq!(topology_cost(text, "square:3x3"), topology_cost(text, "square"));
+
+	let mut maze: Maze = Maze::from(&topology_image(text, "square:3x3".parse().unwrap()));
+	maze.set_topology("square:3x3".parse().unwrap()).unwrap();
+	assert_eq!(maze.solve(1, 1).unwrap().len(), 6);
+	assert_eq!(maze.save_path("./outputs/square3x3.bmp").err(), Option::<MazeError>::None);
+	let img = bmp::open("./outputs/square3x3.bmp").unwrap();
+	// клетката (0, 1) е по пътя, целият ѝ блок е оцветен
+	for (x, y) in [(3, 0), (5, 2), (4, 1)].iter() {
+		assert_eq!(Color::from(img.get_pixel(*x, *y)), PATH_COLOR);
+	}
+	assert_eq!(Color::from(img.get_pixel(9, 0)), WALL_COLOR);
+}
+
+#[test]
+fn test_hex_cells() {
+	// from the top left to the bottom right cell of 3x3 is 4 steps on squares, 3 on hexagons
+	let text = "S..\n...\n..E\n";
+	let square = topology_cost(text, "square:12x12").unwrap();
+	assert_eq!(topology_cost(text, "hex:12x12").unwrap() * 4, square * 3);
+	// (0, 1) and (1, 0) aren't neighbours on squares, but are on hexagons
+	assert_eq!(topology_cost(".S\nE#\n", "square:12x12"), topology_cost("S\nE\n", "square:12x12").map(|cost| cost * 2));
+	assert_eq!(topology_cost(".S\nE#\n", "hex:12x12"), topology_cost("S\nE\n", "hex:12x12"));
+}
+
+#[test]
+fn test_triangle_cells() {
+	// a triangle pointing up has a neighbour below, one pointing down doesn't
+	let text = "S.#\n#.E\n";
+	assert!(topology_cost(text, "square:12x12").is_some());
+	assert_eq!(topology_cost(text, "triangle:12x12"), None);
+	assert!(topology_cost("S#\n.E\n", "triangle:12x12").is_some());
+}
+
+#[test]
+fn test_topology_text_maze() {
+	let mut maze: Maze = text_maze("S.E\n");
+	assert_eq!(maze.set_topology("hex".parse().unwrap()).err().map(|e| e.kind), Some(MazeErrorKind::Config));
+	assert_eq!(maze.set_topology(Topology::default()).err(), Option::<MazeError>::None);
+	assert_eq!(maze.solve(1, 1).unwrap().cost, 2);
+}
+
+// floor tests
+
+fn floors_maze(floors: &[&str]) -> Maze {
+	Maze::stack(floors.iter().map(|text| text_maze(text)).collect()).unwrap()
+}
+
+#[test]
+fn test_stairs_between_floors() {
+	let floors = ["S.=#E\n####=\n", "..=..\n####=\n"];
+	assert_eq!(text_maze(floors[0]).solve(1, 1).err().map(|e| e.kind), Some(MazeErrorKind::Unreachable));
+
+	let mut maze: Maze = floors_maze(&floors);
+	assert_eq!(maze.floors(), 2);
+	let solution = maze.solve(1, 1).unwrap();
+	assert_eq!(solution.cost, 8);
+	assert_eq!(solution.steps[3].coord, Coord::on_floor(0, 2, 1));
+	assert_eq!(solution.steps[7].coord, Coord::on_floor(1, 4, 0));
+	maze.find_path_astar(1, 1).unwrap();
+	assert_eq!(maze.solution().unwrap().cost, 8);
+
+	maze.set_stair_cost(10);
+	assert_eq!(maze.solve(1, 1).unwrap().cost, 26);
+}
+
+#[test]
+fn test_keys_across_floors() {
+	let mut maze: Maze = floors_maze(&["S=.AE\n", "a=...\n"]);
+	let solution = maze.solve(1, 1).unwrap();
+	assert_eq!(solution.cost, 8);
+	assert_eq!(solution.pickups, vec![(text::letter_color('a'), 3)]);
+	assert_eq!(solution.steps[3].coord, Coord::on_floor(0, 0, 1));
+}
+
+#[test]
+fn test_save_floors() {
+	let mut maze: Maze = floors_maze(&["S.=#E\n####=\n", "..=..\n####=\n"]);
+	maze.find_path(1, 1).unwrap();
+	assert_eq!(maze.render_text().unwrap(), "S*=#E\n####=\n\n..=**\n####=\n");
+	assert_eq!(maze.floor_file_name("./outputs/floors.txt", 1), "./outputs/floors.floor1.txt");
+	assert_eq!(maze.save_path("./outputs/floors.txt").err(), Option::<MazeError>::None);
+	assert_eq!(std::fs::read_to_string("./outputs/floors.floor1.txt").unwrap(), "..=**\n####=\n");
+}
+
+#[test]
+fn test_stack_errors() {
+	let stacked = |floors: Vec<Maze>| Maze::stack(floors).err().map(|e| e.kind);
+	assert_eq!(stacked(Vec::new()), Some(MazeErrorKind::Config));
+	assert_eq!(stacked(vec![text_maze("S.E\n"), text_maze("S.\n")]), Some(MazeErrorKind::Config));
+	let img = image_from_rows(&[vec![START_COLOR, END_COLOR, WALL_COLOR]]);
+	assert_eq!(stacked(vec![text_maze("S.E\n"), Maze::from(&img)]), Some(MazeErrorKind::Config));
+}
+
+// portal tests
+
+#[test]
+fn test_portal_jump() {
+	let portal = text::letter_color('p');
+	let mut maze: Maze = text_maze("S.P##P.E\n");
+	assert_eq!(maze.solve(1, 1).err().map(|e| e.kind), Some(MazeErrorKind::Unreachable));
+
+	maze.set_portals(vec![Portal::new(portal, 5)]);
+	let solution = maze.solve(1, 1).unwrap();
+	assert_eq!(solution.cost, 9);
+	assert_eq!(solution.jumps, vec![(portal, 3)]);
+	assert_eq!(solution.steps[3].coord, Coord::new(0, 5));
+	assert_eq!(solution.steps[3].dist, 7);
+}
+
+#[test]
+fn test_portal_areas_touching_at_a_corner() {
+	let portal = text::letter_color('p');
+	let mut maze: Maze = text_maze("SP#\n##P\n##E\n");
+	maze.set_portals(vec![Portal::new(portal, 100)]);
+	maze.set_connectivity(Connectivity::EightCutCorners);
+	let solution = maze.solve(1, 1).unwrap();
+	assert_eq!(solution.cost, 100 + 141 + 100);
+	assert_eq!(solution.jumps, vec![]);
+
+	// скокът е по-евтин от стъпката по диагонал, въпреки че изходът е съседен
+	maze.set_portals(vec![Portal::new(portal, 1)]);
+	let solution = maze.solve(1, 1).unwrap();
+	assert_eq!(solution.cost, 100 + 100 + 100);
+	assert_eq!(solution.jumps, vec![(portal, 2)]);
+}
+
+#[test]
+fn test_portal_shortcut_astar() {
+	let mut maze: Maze = text_maze("SP.........PE\n.............\n");
+	maze.set_portals(vec![Portal::new(text::letter_color('p'), 1)]);
+	maze.find_path(1, 1).unwrap();
+	assert_eq!(maze.solution().unwrap().cost, 3);
+	maze.find_path_astar(1, 1).unwrap();
+	assert_eq!(maze.solution().unwrap().cost, 3);
+}
+
+#[test]
+fn test_locked_portal() {
+	let portal = Portal::locked(text::letter_color('p'), 1, text::letter_color('k'));
+	let mut maze: Maze = text_maze("kS.P#P.E\n");
+	maze.set_portals(vec![portal]);
+	let solution = maze.solve(1, 1).unwrap();
+	assert_eq!(solution.cost, 7);
+	assert_eq!(solution.pickups, vec![(text::letter_color('k'), 1)]);
+
+	let mut maze: Maze = text_maze("S.P#P.E#k\n");
+	maze.set_portals(vec![portal]);
+	assert_eq!(maze.solve(1, 1).err().map(|e| e.kind), Some(MazeErrorKind::Unreachable));
+}
+
+#[test]
+fn test_portal_parse() {
+	let portal: Portal = "#ff00ff:5:0,255,0".parse().unwrap();
+	assert_eq!(portal, Portal::locked(Color { r: 255, g: 0, b: 255 }, 5, Color { r: 0, g: 255, b: 0 }));
+	assert_eq!(portal.to_string(), "#ff00ff:5:#00ff00");
+	assert_eq!("#ff00ff".parse::<Portal>().unwrap(), Portal::new(Color { r: 255, g: 0, b: 255 }, 1));
+	assert_eq!("#ff00ff:x".parse::<Portal>().err().map(|e| e.kind), Some(MazeErrorKind::Config));
+}
+
+#[test]
+fn test_save_portal_jump() {
+	let portal = Color { r: 255, g: 0, b: 255 };
+	let row: Vec<Color> = (0..20).map(|col| match col {
+		0 => START_COLOR,
+		1..=7 | 11..=17 => portal,
+		8..=10 => WALL_COLOR,
+		18 => Color { r: 1, g: 1, b: 1 },
+		_ => END_COLOR
+	}).collect();
+	let mut maze: Maze = Maze::from(&image_from_rows(&vec![row; 7]));
+	maze.set_portals(vec![Portal::new(portal, 10)]);
+	assert_eq!(maze.find_path(20, 20).err(), Option::<MazeError>::None);
+	assert_eq!(maze.save_path("./outputs/portal.bmp").err(), Option::<MazeError>::None);
+
+	// двата края на скока са с номер 1
+	let img = bmp::open("./outputs/portal.bmp").unwrap();
+	assert_eq!(Color::from(img.get_pixel(4, 1)), WALL_COLOR);
+	assert_eq!(Color::from(img.get_pixel(14, 1)), WALL_COLOR);
+	assert_eq!(Color::from(img.get_pixel(11, 0)), PATH_COLOR);
+}
+
+// conveyor tests
+
+const CONVEYOR_COLORS: [Color; 4] = [
+	Color { r: 255, g: 127, b: 39 },
+	Color { r: 34, g: 177, b: 76 },
+	Color { r: 63, g: 72, b: 204 },
+	Color { r: 163, g: 73, b: 164 },
+];
+
+// a maze of rows of "S", "E", "#", "." and the arrows "^", ">", "v", "<" for the conveyors
+fn conveyor_maze(rows: &[&str]) -> Maze {
+	let rows: Vec<Vec<Color>> = rows.iter().map(|row| row.chars().map(|c| match c {
+		'S' => START_COLOR,
+		'E' => END_COLOR,
+		'#' => WALL_COLOR,
+		'^' => CONVEYOR_COLORS[0],
+		'>' => CONVEYOR_COLORS[1],
+		'v' => CONVEYOR_COLORS[2],
+		'<' => CONVEYOR_COLORS[3],
+		_ => Color { r: 1, g: 1, b: 1 }
+	}).collect()).collect();
+
+	let palette = Palette { conveyors: CONVEYOR_COLORS.map(Some), ..Default::default() };
+	Maze::new(&image_from_rows(&rows), palette)
+}
+
+#[test]
+fn test_conveyor_one_way() {
+	assert_eq!(conveyor_maze(&["S>>>E"]).solve(1, 1).unwrap().cost, 4);
+	assert_eq!(conveyor_maze(&["S<<<E"]).solve(1, 1).err().map(|e| e.kind), Some(MazeErrorKind::Unreachable));
+	// against the belt the path goes around it, along it the belt is taken
+	assert_eq!(conveyor_maze(&["S.<.E", "....."]).solve(1, 1).unwrap().cost, 6);
+	let solution = conveyor_maze(&["E.<.S", "....."]).solve(1, 1).unwrap();
+	assert_eq!(solution.cost, 4);
+	assert_eq!(solution.coords().cloned().collect::<Vec<Coord>>(), (0..5).rev().map(|col| Coord::new(0, col)).collect::<Vec<Coord>>());
+}
+
+#[test]
+fn test_conveyor_exit_direction() {
+	// the conveyor down can be entered from the side, but only left downwards
+	let mut maze: Maze = conveyor_maze(&["SvE", "..."]);
+	let solution = maze.solve(1, 1).unwrap();
+	assert_eq!(solution.cost, 4);
+	assert_eq!(solution.steps[2].coord, Coord::new(1, 1));
+	maze.find_path_astar(1, 1).unwrap();
+	assert_eq!(maze.solution().unwrap().cost, 4);
+}
+
+#[test]
+fn test_conveyor_palette() {
+	let palette: Palette = "conveyor_left = \"#a349a4\"\nconveyor_up = [255, 127, 39]\n".parse().unwrap();
+	assert_eq!(palette.conveyors, [Some(CONVEYOR_COLORS[0]), None, None, Some(CONVEYOR_COLORS[3])]);
+	assert_eq!(palette.conveyor(&CONVEYOR_COLORS[3]), Some(Direction::Left));
+	assert_eq!("conveyor_sideways = \"#a349a4\"\n".parse::<Palette>().err().map(|e| e.kind), Some(MazeErrorKind::Config));
+}
+
+// schedule tests
+
+#[test]
+fn test_wait_for_timed_door() {
+	let door = text::letter_color('d');
+	// пред вратата няма къде да се ходи, остава само да се чака
+	let mut maze: Maze = text_maze("SDE\n");
+	maze.set_schedules(vec![Schedule::new(door, 6, (3, 4))]);
+	let solution = maze.solve(1, 1).unwrap();
+	assert_eq!(solution.cost, 4);
+	assert_eq!(solution.waits().collect::<Vec<usize>>(), vec![1, 2]);
+	assert_eq!(solution.steps[3].coord, Coord::new(0, 1));
+	assert!(solution.steps.iter().enumerate().all(|(i, step)| step.time == i as u32));
+	maze.find_path_astar(1, 1).unwrap();
+	assert_eq!(maze.solution().unwrap().cost, 4);
+
+	// open on the first tick it is reached, no waiting
+	maze.set_schedules(vec![Schedule::new(door, 4, (1, 2))]);
+	let solution = maze.solve(1, 1).unwrap();
+	assert_eq!(solution.cost, 2);
+	assert_eq!(solution.waits().count(), 0);
+}
+
+#[test]
+fn test_timed_door_or_detour() {
+	let mut maze: Maze = text_maze("S.D.E\n.....\n");
+	// the door opens too late, going around is cheaper than waiting
+	maze.set_schedules(vec![Schedule::new(text::letter_color('d'), 10, (9, 10))]);
+	assert_eq!(maze.solve(1, 1).unwrap().cost, 6);
+	maze.set_schedules(vec![Schedule::new(text::letter_color('d'), 10, (3, 4))]);
+	let solution = maze.solve(1, 1).unwrap();
+	assert_eq!(solution.cost, 5);
+	assert_eq!(solution.waits().count(), 1);
+}
+
+#[test]
+fn test_schedule_parse() {
+	let schedules = Schedule::parse_all("[\"#ff7f27\"]\nperiod = 6\nopen = [0, 2]\n[schedule.\"0,0,255\"]\nperiod = 3\nopen = [2, 3]\n").unwrap();
+	assert_eq!(schedules, vec![
+		Schedule::new(Color { r: 255, g: 127, b: 39 }, 6, (0, 2)),
+		Schedule::new(Color { r: 0, g: 0, b: 255 }, 3, (2, 3)),
+	]);
+	assert_eq!(Schedule::parse_all("{\"#ff7f27\": {\"period\": 6, \"open\": [0, 2]}}").unwrap(), schedules[..1].to_vec());
+	assert!(schedules[0].is_open(7) && !schedules[0].is_open(8));
+
+	for text in ["[\"#ff7f27\"]\nperiod = 6\n", "[\"#ff7f27\"]\nperiod = 6\nopen = [2, 7]\n", "[\"#ff7f27\"]\nperiod = 6\nopen = [0, 2]\nphase = 1\n"].iter() {
+		assert_eq!(Schedule::parse_all(text).err().map(|e| e.kind), Some(MazeErrorKind::Config), "{}", text);
+	}
+
+	let mut maze: Maze = text_maze("S.DE\n");
+	maze.set_schedules(vec![Schedule::new(text::letter_color('d'), 9973, (0, 1)), Schedule::new(text::letter_color('e'), 9967, (0, 1))]);
+	assert_eq!(maze.solve(1, 1).err().map(|e| e.kind), Some(MazeErrorKind::Config));
+
+	// 9000 ticks of a 4000 pixel maze need too many layers
+	let mut maze: Maze = text_maze(&format!("S{}DE\n", ".".repeat(3997)));
+	maze.set_schedules(vec![Schedule::new(text::letter_color('d'), 9000, (0, 1))]);
+	let e = maze.solve(1, 1).unwrap_err();
+	assert_eq!(e.kind, MazeErrorKind::Config);
+	assert!(e.to_string().contains("9000 layers"), "{}", e);
+}
+
+#[test]
+fn test_save_waits() {
+	let timed = Color { r: 255, g: 127, b: 39 };
+	let img = image_from_rows(&[vec![START_COLOR, Color { r: 1, g: 1, b: 1 }, timed, END_COLOR]]);
+	let mut maze: Maze = Maze::from(&img);
+	maze.set_schedules(vec![Schedule::new(timed, 4, (3, 4))]);
+	maze.find_path(20, 20).unwrap();
+	assert_eq!(maze.save_path("./outputs/waits.bmp").err(), Option::<MazeError>::None);
+
+	let img = bmp::open("./outputs/waits.bmp").unwrap();
+	assert_eq!(Color::from(img.get_pixel(1, 0)), WAIT_COLOR);
+	assert_eq!(Color::from(img.get_pixel(2, 0)), PATH_COLOR);
+}
+
+// cost model tests
+
+#[test]
+fn test_uniform_cost() {
+	let mut maze: Maze = text_maze("S9E\n...\n");
+	assert_eq!(maze.solve(1, 1).unwrap().cost, 4);
+	maze.set_cost_model(Arc::new(UniformCost(1)));
+	let solution = maze.solve(1, 1).unwrap();
+	assert_eq!(solution.cost, 2);
+	assert_eq!(solution.steps[1].coord, Coord::new(0, 1));
+
+	maze.set_cost_model(Arc::new(UniformCost(3)));
+	maze.set_connectivity(Connectivity::Eight);
+	assert_eq!(maze.solve(1, 1).unwrap().cost, 2 * 3 * STRAIGHT_COST);
+}
+
+#[test]
+fn test_uniform_cost_overflow() {
+	let mut maze: Maze = text_maze("S.E\n");
+	maze.set_cost_model(Arc::new(UniformCost(u32::MAX / 2)));
+	assert_eq!(maze.solve(1, 1).unwrap().cost, u32::MAX - 1);
+
+	maze.set_connectivity(Connectivity::Eight);
+	assert_eq!(maze.solve(1, 1).unwrap_err().kind, MazeErrorKind::Config);
+
+	let mut maze: Maze = text_maze("S..E\n");
+	maze.set_cost_model(Arc::new(UniformCost(u32::MAX / 2)));
+	assert_eq!(maze.solve(1, 1).unwrap_err().kind, MazeErrorKind::Config);
+	assert_eq!(maze.find_path_astar(1, 1).unwrap_err().kind, MazeErrorKind::Config);
+}
+
+#[test]
+fn test_luminance_cost() {
+	let cell = |color: Color| Cell { coord: Coord::new(0, 0), color, free: false, heading: None };
+	let red = cell(Color { r: 255, g: 0, b: 0 });
+	let black = cell(Color { r: 0, g: 0, b: 0 });
+	assert_eq!(LuminanceCost { inverted: false }.cost(&black, &red, &[]), Some(76));
+	assert_eq!(LuminanceCost { inverted: true }.cost(&black, &red, &[]), Some(179));
+	assert_eq!(LuminanceCost { inverted: false }.cost(&red, &black, &[]), Some(1));
+
+	let mut maze: Maze = text_maze("S9E\n...\n");
+	maze.set_cost_model(Arc::new(LuminanceCost { inverted: true }));
+	// тъмните сиви клетки са скъпи, така че се минава направо през 9
+	let solution = maze.solve(1, 1).unwrap();
+	assert_eq!(solution.steps[1].coord, Coord::new(0, 1));
+	maze.find_path_astar(1, 1).unwrap();
+	assert_eq!(maze.solution().unwrap().cost, solution.cost);
+}
+
+#[test]
+fn test_table_cost() {
+	let table = TableCost::parse("\"1,1,1\" = 7\n\"#090909\" = 2\ndefault = 1\n").unwrap();
+	assert_eq!(table.costs.len(), 2);
+	assert_eq!(table.default, Some(1));
+	assert_eq!(TableCost::parse("\"#090909\" = -2\n").err().map(|e| e.kind), Some(MazeErrorKind::Config));
+	assert_eq!(TableCost::parse("\"#0909\" = 2\n").err().map(|e| e.kind), Some(MazeErrorKind::Config));
+
+	let mut maze: Maze = text_maze("S9E\n...\n");
+	maze.set_cost_model(Arc::new(table.clone()));
+	assert_eq!(maze.solve(1, 1).unwrap().cost, 3);
+
+	// without a default only the colours of the table can be entered
+	maze.set_cost_model(Arc::new(TableCost { default: None, ..table }));
+	assert_eq!(maze.solve(1, 1).err().map(|e| e.kind), Some(MazeErrorKind::Unreachable));
+}
+
+#[test]
+fn test_turn_penalty() {
+	let mut maze: Maze = text_maze("S..\n...\n..E\n");
+	maze.set_cost_model(Arc::new(TurnPenalty { base: Arc::new(GreyCost), penalty: 10 }));
+	let solution = maze.solve(1, 1).unwrap();
+	assert_eq!(solution.cost, 14);
+	let turns = solution.steps.windows(3)
+		.filter(|w| (w[1].coord.row as i32 - w[0].coord.row as i32, w[1].coord.col as i32 - w[0].coord.col as i32)
+			!= (w[2].coord.row as i32 - w[1].coord.row as i32, w[2].coord.col as i32 - w[1].coord.col as i32))
+		.count();
+	assert_eq!(turns, 1);
+	maze.find_path_astar(1, 1).unwrap();
+	assert_eq!(maze.solution().unwrap().cost, 14);
+
+	maze.set_cost_model(Arc::new(GreyCost));
+	assert_eq!(maze.solve(1, 1).unwrap().cost, 4);
+}
+
+// every step is cheap once a key is held
+#[derive(Debug)]
+struct KeyedCost;
+
+impl CostModel for KeyedCost {
+	fn cost(&self, _from: &Cell, _to: &Cell, keys: &[Color]) -> Option<u32> {
+		match keys.is_empty() {
+			true => Some(10),
+			false => Some(1)
+		}
+	}
+}
+
+#[test]
+fn test_custom_cost_model() {
+	let mut maze: Maze = text_maze("Sa..E\n");
+	maze.set_cost_model(Arc::new(KeyedCost));
+	assert_eq!(maze.solve(1, 1).unwrap().cost, 13);
+	// без долна граница A* се държи като Дейкстра
+	maze.find_path_astar(1, 1).unwrap();
+	assert_eq!(maze.solution().unwrap().cost, 13);
+}
@@ -0,0 +1,163 @@
+// Plain text mazes, readable in a diff:
+//
+//     #    wall
+//     .    free, costs 1
+//     1-9  grey with that weight
+//     S E  start and end
+//     a-z  key of any size, even a single character
+//     A-Z  door opened by the key with the same letter (except S and E)
+//     =    stairs to the floor above or below, see Maze::stack
+//
+// Every letter gets its own colour, so a text maze is solved exactly like an image.
+
+use super::{Color, Maze, MazeError, MazeErrorKind, MazeResult, Palette, PixelType, Solution, STAIR_COLOR};
+use super::format::RawImage;
+use super::ImageFormat;
+
+const PATH_CHAR: char = '*';
+
+fn text_error<T: AsRef<str>>(details: T) -> MazeError {
+	MazeError::new(MazeErrorKind::Decode, details)
+}
+
+fn is_maze_char(c: char) -> bool {
+	c == '#' || c == '.' || c == '=' || c == 'S' || c == 'E' || ('1'..='9').contains(&c) || c.is_ascii_alphabetic()
+}
+
+// only legend characters and whitespace
+pub(crate) fn is_text_maze(bytes: &[u8]) -> bool {
+	bytes.iter().any(|b| !b.is_ascii_whitespace())
+		&& bytes.iter().all(|b| b.is_ascii_whitespace() || is_maze_char(*b as char))
+}
+
+// Colour of a key/door letter. The hues are spread evenly around the colour wheel
+// (and shifted by half a step so that no letter is pure red like the path).
+pub(crate) fn letter_color(letter: char) -> Color {
+	let i = (letter.to_ascii_lowercase() as u32).saturating_sub('a' as u32).min(25);
+	let hue = (2 * i + 1) * 6 * 256 / 52;
+	let x = (hue % 256) as u8;
+
+	let (r, g, b) = match hue / 256 {
+		0 => (255, x, 0),
+		1 => (255 - x, 255, 0),
+		2 => (0, 255, x),
+		3 => (0, 255 - x, 255),
+		4 => (x, 0, 255),
+		_ => (255, 0, 255 - x),
+	};
+	Color { r, g, b }
+}
+
+fn grey(weight: u8) -> Color {
+	Color { r: weight, g: weight, b: weight }
+}
+
+impl Maze {
+	// Parses a text maze. Lowercase letters are keys whatever their size, so the key size
+	// passed to find_path is ignored. Shorter lines are padded with walls.
+	// The stairs get the stair colour of the palette, or STAIR_COLOR if it has none.
+	pub fn parse_text(text: &str, mut palette: Palette) -> MazeResult<Maze> {
+		let stair = *palette.stair.get_or_insert(STAIR_COLOR);
+		let lines: Vec<&str> = text.lines()
+			.map(|line| line.trim_end())
+			.filter(|line| !line.is_empty())
+			.collect();
+
+		let width = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+		if width == 0 {
+			return Err(text_error("Text maze is empty"));
+		}
+
+		let mut colors: Vec<Color> = Vec::with_capacity(width * lines.len());
+		let mut marked_keys: Vec<bool> = Vec::with_capacity(width * lines.len());
+		for (row, line) in lines.iter().enumerate() {
+			let chars: Vec<char> = line.chars().collect();
+			for col in 0..width {
+				let c = chars.get(col).cloned().unwrap_or('#');
+				colors.push(match c {
+					'#' => palette.wall,
+					'.' => grey(1),
+					'=' => stair,
+					'S' => palette.start,
+					'E' => palette.end,
+					'1'..='9' => grey(c as u8 - b'0'),
+					c if c.is_ascii_alphabetic() => letter_color(c),
+					c => return Err(text_error(format!("Unknown character '{}' at line {}, column {}", c, row + 1, col + 1)))
+				});
+				marked_keys.push(c.is_ascii_lowercase());
+			}
+		}
+
+		let img = RawImage {
+			width: width as u32,
+			height: lines.len() as u32,
+			colors,
+		};
+		let mut maze = Maze::from_raw(img, ImageFormat::Text, palette);
+		maze.marked_keys = Some(marked_keys);
+		Ok(maze)
+	}
+
+	// Writes the maze back as text, with the path found by the last search drawn as '*'
+	// over the free cells it crosses. Fails for colours that have no character.
+	// Floors are separated by an empty line.
+	pub fn render_text(&self) -> MazeResult<String> {
+		let solutions = match self.end.is_some() {
+			true => vec![self.solution()?],
+			false => Vec::new()
+		};
+		let floors: Vec<String> = (0..self.floors())
+			.map(|floor| self.draw_text(&solutions, floor))
+			.collect::<MazeResult<Vec<String>>>()?;
+		Ok(floors.join("\n"))
+	}
+
+	pub(crate) fn draw_text(&self, solutions: &[Solution], floor: u32) -> MazeResult<String> {
+		let mut on_path: Vec<bool> = vec![false; self.colors.len()];
+		for coord in solutions.iter().flat_map(|solution| solution.coords()) {
+			on_path[self.pixel_indx(coord)?] = true;
+		}
+
+		let letters: Vec<(Color, char)> = (b'a'..=b'z').map(|c| (letter_color(c as char), c as char)).collect();
+
+		let plane = (self.width * self.height) as usize;
+		let mut text = String::with_capacity(plane + self.height as usize);
+		for indx in (floor as usize * plane)..((floor as usize + 1) * plane) {
+			let color = &self.colors[indx];
+			let c = match self.palette.classify(color) {
+				PixelType::WALL => '#',
+				PixelType::STAIR => '=',
+				PixelType::START => 'S',
+				PixelType::END => 'E',
+				PixelType::FREE if on_path[indx] => PATH_CHAR,
+				PixelType::FREE => match super::grey_level(color) {
+					1 => '.',
+					weight @ 2..=9 => (b'0' + weight) as char,
+					weight => return Err(text_error(format!("Grey weight {} at {:?} can't be written as text", weight, self.coord_at(indx))))
+				},
+				_ => {
+					let letter = letters.iter()
+						.find(|(letter_color, _)| self.palette.matches(letter_color, color))
+						.map(|(_, letter)| *letter)
+						.ok_or_else(|| text_error(format!("Colour {:?} at {:?} can't be written as text", color, self.coord_at(indx))))?;
+
+					let is_key = match &self.marked_keys {
+						Some(marks) => marks[indx],
+						None => self.p_types[indx] == PixelType::KEY
+					};
+					match is_key {
+						true => letter,
+						false => letter.to_ascii_uppercase()
+					}
+				}
+			};
+
+			text.push(c);
+			if (indx + 1) % self.width as usize == 0 {
+				text.push('\n');
+			}
+		}
+
+		Ok(text)
+	}
+}
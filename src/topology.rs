@@ -0,0 +1,221 @@
+// How the cells of a maze are laid out in the image. The search works on a grid of cells
+// addressed by (row, col) like the pixels of a plain maze; the topology says which cells
+// are neighbours, which pixel gives a cell its colour and which pixels draw it.
+// Square cells of 1x1 pixel are the plain pixel grid.
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::{Color, Connectivity, Coord, MazeError, MazeResult};
+use super::config::config_error;
+use super::format::RawImage;
+
+const HEX_EVEN_ROW_STEPS: [(i32, i32); 6] = [(0, -1), (0, 1), (-1, -1), (-1, 0), (1, -1), (1, 0)];
+const HEX_ODD_ROW_STEPS: [(i32, i32); 6] = [(0, -1), (0, 1), (-1, 0), (-1, 1), (1, 0), (1, 1)];
+const TRIANGLE_UP_STEPS: [(i32, i32); 3] = [(0, -1), (0, 1), (1, 0)];
+const TRIANGLE_DOWN_STEPS: [(i32, i32); 3] = [(0, -1), (0, 1), (-1, 0)];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CellShape {
+	#[default]
+	Square,
+	// pointy-top hexagons in rows, every odd row shifted right by half a cell
+	Hex,
+	// triangles pointing up and down in turn, the top left one points up
+	Triangle,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Topology {
+	pub shape: CellShape,
+	// Pixels of the block of a cell. Square and hex cells are that far apart, a hexagon
+	// is a third taller than its block and overlaps the rows above and below. A triangle
+	// fills its block, the triangles of a row are half a block apart.
+	pub cell_width: u32,
+	pub cell_height: u32,
+}
+
+impl Default for Topology {
+	fn default() -> Self {
+		Topology { shape: CellShape::Square, cell_width: 1, cell_height: 1 }
+	}
+}
+
+// "square", "hex" or "triangle", with an optional ":<width>x<height>" cell size (default 1x1)
+impl FromStr for Topology {
+	type Err = MazeError;
+
+	fn from_str(text: &str) -> MazeResult<Topology> {
+		let error = || config_error(format!("Expected square, hex or triangle with an optional :WxH cell size, found '{}'", text));
+		let lower = text.trim().to_ascii_lowercase();
+		let (shape, size) = match lower.split_once(':') {
+			Some((shape, size)) => (shape, Some(size)),
+			None => (lower.as_str(), None)
+		};
+
+		let shape = match shape {
+			"square" => CellShape::Square,
+			"hex" => CellShape::Hex,
+			"triangle" => CellShape::Triangle,
+			_ => return Err(error())
+		};
+		let (cell_width, cell_height) = match size.map(|size| size.split_once('x')) {
+			None => (1, 1),
+			Some(Some((width, height))) => match (width.parse(), height.parse()) {
+				(Ok(width), Ok(height)) if width > 0 && height > 0 => (width, height),
+				_ => return Err(error())
+			},
+			Some(None) => return Err(error())
+		};
+		Ok(Topology { shape, cell_width, cell_height })
+	}
+}
+
+impl fmt::Display for Topology {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		let shape = match self.shape {
+			CellShape::Square => "square",
+			CellShape::Hex => "hex",
+			CellShape::Triangle => "triangle",
+		};
+		write!(fmt, "{}:{}x{}", shape, self.cell_width, self.cell_height)
+	}
+}
+
+impl Topology {
+	// the plain pixel grid
+	pub(crate) fn is_pixels(&self) -> bool {
+		*self == Topology::default()
+	}
+
+	// (width, height) in cells of the grid laid over an image, cells that don't fit whole are left out
+	pub(crate) fn grid_size(&self, width: u32, height: u32) -> (u32, u32) {
+		let (w, h) = (self.cell_width, self.cell_height);
+		match self.shape {
+			CellShape::Square => (width / w, height / h),
+			// нечетните редове са изместени с половин клетка
+			CellShape::Hex => (width.saturating_sub(w / 2) / w, height / h),
+			CellShape::Triangle => ((2 * width / w).saturating_sub(1), height / h),
+		}
+	}
+
+	// Diagonal moves only make sense between square cells, the others have a fixed set of neighbours.
+	pub(crate) fn connectivity(&self, connectivity: Connectivity) -> Connectivity {
+		match self.shape {
+			CellShape::Square => connectivity,
+			_ => Connectivity::Four
+		}
+	}
+
+	// (row, col) offsets of the neighbours of a cell
+	pub(crate) fn steps(&self, connectivity: Connectivity, c: &Coord) -> &'static [(i32, i32)] {
+		match self.shape {
+			CellShape::Square => connectivity.steps(),
+			CellShape::Hex if c.row.is_multiple_of(2) => &HEX_EVEN_ROW_STEPS,
+			CellShape::Hex => &HEX_ODD_ROW_STEPS,
+			CellShape::Triangle if (c.row + c.col).is_multiple_of(2) => &TRIANGLE_UP_STEPS,
+			CellShape::Triangle => &TRIANGLE_DOWN_STEPS,
+		}
+	}
+
+	// whether moving between two cells changes the row and the column at once,
+	// which only costs more than a straight step between square cells
+	pub(crate) fn is_diagonal(&self, (dr, dc): (i32, i32)) -> bool {
+		self.shape == CellShape::Square && dr != 0 && dc != 0
+	}
+
+	// Lower bound of the number of steps between cells dr rows and dc columns apart
+	// is the Chebyshev distance if a step can change both, the Manhattan one otherwise.
+	pub(crate) fn has_diagonal_steps(&self, connectivity: Connectivity) -> bool {
+		match self.shape {
+			CellShape::Square => connectivity != Connectivity::Four,
+			CellShape::Hex => true,
+			CellShape::Triangle => false,
+		}
+	}
+
+	// top left pixel of the block of a cell, as (x, y)
+	fn origin(&self, c: &Coord) -> (u32, u32) {
+		let (w, h) = (self.cell_width, self.cell_height);
+		match self.shape {
+			CellShape::Square => (c.col * w, c.row * h),
+			CellShape::Hex => (c.col * w + (c.row % 2) * (w / 2), c.row * h),
+			CellShape::Triangle => (c.col * w / 2, c.row * h),
+		}
+	}
+
+	// the pixel that gives a cell its colour: the centre, or the centroid of a triangle
+	fn sample(&self, c: &Coord) -> (u32, u32) {
+		let (x, y) = self.origin(c);
+		let (w, h) = (self.cell_width, self.cell_height);
+		match self.shape {
+			CellShape::Triangle if (c.row + c.col).is_multiple_of(2) => (x + w / 2, y + 2 * h / 3),
+			CellShape::Triangle => (x + w / 2, y + h / 3),
+			_ => (x + w / 2, y + h / 2),
+		}
+	}
+
+	// pixels that draw a cell, as (x, y), only the ones inside an image of this size
+	pub(crate) fn pixels(&self, c: &Coord, width: u32, height: u32) -> Vec<(u32, u32)> {
+		let (x0, y0) = self.origin(c);
+		let (w, h) = (self.cell_width as i64, self.cell_height as i64);
+		// координатите са удвоени, за да са центровете на пикселите цели числа
+		let inside = |x: i64, y: i64| -> bool {
+			match self.shape {
+				CellShape::Square => true,
+				CellShape::Hex => {
+					let (dx, dy) = ((2 * x + 1 - 2 * x0 as i64 - w).abs(), (2 * y + 1 - 2 * y0 as i64 - h).abs());
+					dx <= w && 3 * dy * w <= 4 * h * w - 2 * h * dx
+				},
+				CellShape::Triangle => {
+					let dy = y - y0 as i64;
+					let rows_from_apex = match (c.row + c.col) % 2 {
+						0 => dy,
+						_ => h - 1 - dy
+					};
+					(2 * (x - x0 as i64) + 1 - w).abs() * 2 * h <= (2 * rows_from_apex + 1) * w
+				}
+			}
+		};
+
+		// шестоъгълникът стърчи с една шеста от височината над и под блока си
+		let spill = match self.shape {
+			CellShape::Hex => h / 6 + 1,
+			_ => 0
+		};
+		let mut pixels: Vec<(u32, u32)> = Vec::new();
+		for y in (y0 as i64 - spill).max(0)..(y0 as i64 + h + spill).min(height as i64) {
+			for x in (x0 as i64)..(x0 as i64 + w).min(width as i64) {
+				if inside(x, y) {
+					pixels.push((x as u32, y as u32));
+				}
+			}
+		}
+		pixels
+	}
+
+	// the cell grid of an image, every cell with the colour of its sample pixel
+	pub(crate) fn cells(&self, img: &RawImage) -> RawImage {
+		if self.is_pixels() {
+			return img.clone();
+		}
+
+		let (width, height) = self.grid_size(img.width, img.height);
+		let mut colors: Vec<Color> = Vec::with_capacity((width * height) as usize);
+		for row in 0..height {
+			for col in 0..width {
+				let (x, y) = self.sample(&Coord::new(row, col));
+				colors.push(img.colors[(y.min(img.height - 1) * img.width + x.min(img.width - 1)) as usize]);
+			}
+		}
+		RawImage { width, height, colors }
+	}
+
+	// Pixel bounding box (top left, bottom right) of the block of cells from min to max,
+	// with the pixel row and column in Coord.
+	pub(crate) fn pixel_box(&self, min: &Coord, max: &Coord) -> (Coord, Coord) {
+		let (x0, y0) = self.origin(min);
+		let (x1, y1) = self.origin(max);
+		(Coord::new(y0, x0), Coord::new(y1 + self.cell_height - 1, x1 + self.cell_width - 1))
+	}
+}